@@ -0,0 +1,257 @@
+use crate::audio::resampler::Resampler;
+use crate::audio::DEFAULT_SAMPLE_RATE;
+use dmc::Dmc;
+use expansion::ExpansionOutput;
+use frame_counter::{FrameCounter, FrameSignal};
+use mixer::{Channel, ChannelControls, CHANNELS};
+use noise::Noise;
+use pulse::{Pulse, PulseChannel};
+use triangle::Triangle;
+
+pub mod dmc;
+pub mod envelope;
+pub mod expansion;
+pub mod frame_counter;
+pub mod length_counter;
+pub mod mixer;
+pub mod noise;
+pub mod pulse;
+pub mod triangle;
+
+pub const CPU_FREQUENCY: f64 = 1_789_773.0;
+
+pub struct Apu {
+    pulse1: Pulse,
+    pulse2: Pulse,
+    triangle: Triangle,
+    noise: Noise,
+    dmc: Dmc,
+
+    frame_counter: FrameCounter,
+
+    channel_controls: ChannelControls,
+    resampler: Resampler,
+    // One resampler per channel while recording stems, each channel mixed on its own
+    stems: Option<Vec<Resampler>>,
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        Apu {
+            pulse1: Pulse::new(PulseChannel::One),
+            pulse2: Pulse::new(PulseChannel::Two),
+            triangle: Triangle::default(),
+            noise: Noise::new(),
+            dmc: Dmc::new(),
+            frame_counter: FrameCounter::default(),
+            channel_controls: ChannelControls::default(),
+            resampler: Resampler::new(CPU_FREQUENCY, DEFAULT_SAMPLE_RATE),
+            stems: None,
+        }
+    }
+
+    // Takes the CPU cycle the write lands on since $4017 writes depend on its parity
+    pub fn cpu_write(&mut self, addr: u16, value: u8, cpu_cycle: usize) {
+        match addr {
+            0x4000..=0x4003 => self.pulse1.write(addr & 3, value),
+            0x4004..=0x4007 => self.pulse2.write(addr & 3, value),
+            0x4008..=0x400B => self.triangle.write(addr & 3, value),
+            0x400C..=0x400F => self.noise.write(addr & 3, value),
+            0x4010..=0x4013 => self.dmc.write(addr & 3, value),
+            0x4015 => self.write_status(value),
+            0x4017 => self.frame_counter.write(value, cpu_cycle),
+            _ => (),
+        }
+    }
+
+    fn write_status(&mut self, value: u8) {
+        self.pulse1.length_counter.set_enabled(value & 1 != 0);
+        self.pulse2.length_counter.set_enabled(value & 2 != 0);
+        self.triangle.length_counter.set_enabled(value & 4 != 0);
+        self.noise.length_counter.set_enabled(value & 8 != 0);
+        self.dmc.set_enabled(value & 0x10 != 0);
+    }
+
+    // The reset button silences every channel, as if $4015 was written with 0
+    pub fn reset(&mut self) {
+        self.write_status(0);
+    }
+
+    // Only $4015 is readable, reading it acknowledges the frame IRQ
+    pub fn read_status(&mut self) -> u8 {
+        let status = (self.pulse1.length_counter.is_active() as u8)
+            | (self.pulse2.length_counter.is_active() as u8) << 1
+            | (self.triangle.length_counter.is_active() as u8) << 2
+            | (self.noise.length_counter.is_active() as u8) << 3
+            | (self.dmc.is_active() as u8) << 4
+            | (self.frame_counter.irq_flag as u8) << 6
+            | (self.dmc.irq_flag as u8) << 7;
+
+        self.frame_counter.irq_flag = false;
+        status
+    }
+
+    pub fn irq_line(&self) -> bool {
+        self.frame_counter.irq_flag || self.dmc.irq_flag
+    }
+
+    // The DMC's memory reader fetches through the CPU bus, so the bus does it for us
+    pub fn dmc_dma_request(&self) -> Option<u16> {
+        self.dmc.dma_request()
+    }
+
+    pub fn dmc_dma_fill(&mut self, value: u8) {
+        self.dmc.dma_fill(value);
+    }
+
+    fn clock_quarter_frame(&mut self) {
+        self.pulse1.envelope.clock();
+        self.pulse2.envelope.clock();
+        self.noise.envelope.clock();
+        self.triangle.clock_linear_counter();
+    }
+
+    fn clock_half_frame(&mut self) {
+        self.pulse1.length_counter.clock();
+        self.pulse2.length_counter.clock();
+        self.triangle.length_counter.clock();
+        self.noise.length_counter.clock();
+        self.pulse1.clock_sweep();
+        self.pulse2.clock_sweep();
+    }
+
+    // Raw levels in the order of CHANNELS, expansion audio is already in output units
+    fn channel_levels(&self, expansion: &ExpansionOutput) -> [f32; CHANNELS.len()] {
+        [
+            self.pulse1.output() as f32,
+            self.pulse2.output() as f32,
+            self.triangle.output() as f32,
+            self.noise.output() as f32,
+            self.dmc.output() as f32,
+            expansion.vrc6,
+            expansion.vrc7,
+            expansion.fds,
+            expansion.mmc5,
+            expansion.namco_163,
+            expansion.sunsoft_5b,
+        ]
+    }
+
+    fn mix_levels(levels: &[f32; CHANNELS.len()]) -> f32 {
+        mixer::mix(
+            levels[Channel::Pulse1 as usize],
+            levels[Channel::Pulse2 as usize],
+            levels[Channel::Triangle as usize],
+            levels[Channel::Noise as usize],
+            levels[Channel::Dmc as usize],
+        ) + levels[Channel::Vrc6 as usize..].iter().sum::<f32>()
+    }
+
+    fn mix_alone(levels: &[f32; CHANNELS.len()], channel: usize) -> f32 {
+        let mut alone = [0.0; CHANNELS.len()];
+        alone[channel] = levels[channel];
+        Apu::mix_levels(&alone)
+    }
+
+    // The DACs aren't linear, so scaling a level before mixing doesn't scale what you hear
+    // and changes how loud the other channels sound. Once any control is touched every
+    // channel is mixed alone and scaled afterwards, which is also exactly what its stem holds
+    fn output(&self, levels: &[f32; CHANNELS.len()]) -> f32 {
+        let gains = CHANNELS.map(|channel| self.channel_controls.get_gain(channel));
+        if gains.iter().all(|&gain| gain == 1.0) {
+            return Apu::mix_levels(levels);
+        }
+
+        (0..CHANNELS.len())
+            .filter(|&channel| gains[channel] != 0.0)
+            .map(|channel| gains[channel] * Apu::mix_alone(levels, channel))
+            .sum()
+    }
+
+    pub fn get_channel_controls(&self) -> &ChannelControls {
+        &self.channel_controls
+    }
+
+    pub fn get_channel_controls_mut(&mut self) -> &mut ChannelControls {
+        &mut self.channel_controls
+    }
+
+    // Runs one CPU cycle worth of APU, mixing in the cartridge's sound chip output. The
+    // pulse timers only tick on odd CPU cycles
+    pub fn clock(&mut self, cpu_cycle: usize, expansion: &ExpansionOutput) {
+        let signal = self.frame_counter.clock(cpu_cycle);
+        if signal.contains(FrameSignal::QUARTER) {
+            self.clock_quarter_frame();
+        }
+        if signal.contains(FrameSignal::HALF) {
+            self.clock_half_frame();
+        }
+
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+        if cpu_cycle & 1 == 1 {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+
+        let levels = self.channel_levels(expansion);
+        self.resampler.push(self.output(&levels));
+
+        // Stems ignore the channel controls, they're always the raw channel
+        if let Some(stems) = &mut self.stems {
+            for (channel, stem) in stems.iter_mut().enumerate() {
+                stem.push(Apu::mix_alone(&levels, channel));
+            }
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.resampler = Resampler::new(CPU_FREQUENCY, sample_rate);
+        if self.stems.is_some() {
+            self.set_stems_enabled(true);
+        }
+    }
+
+    pub fn set_stems_enabled(&mut self, enabled: bool) {
+        let sample_rate = self.get_sample_rate();
+        self.stems = if enabled {
+            Some(
+                CHANNELS
+                    .iter()
+                    .map(|_| Resampler::new(CPU_FREQUENCY, sample_rate))
+                    .collect(),
+            )
+        } else {
+            None
+        };
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.resampler.get_sample_rate()
+    }
+
+    // Filtered samples produced since the last call, centered around 0
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        let mut samples = Vec::new();
+        self.resampler.read_samples(&mut samples);
+        samples
+    }
+
+    pub fn get_stems_enabled(&self) -> bool {
+        self.stems.is_some()
+    }
+
+    // Same as take_samples for every channel in the order of CHANNELS, empty without stems
+    pub fn take_stem_samples(&mut self) -> Vec<Vec<f32>> {
+        self.stems
+            .iter_mut()
+            .flatten()
+            .map(|stem| {
+                let mut samples = Vec::new();
+                stem.read_samples(&mut samples);
+                samples
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,55 @@
+// Volume envelope shared by the pulse and noise channels, a decaying 4 bit volume
+// or a constant one
+#[derive(Default)]
+pub struct Envelope {
+    start: bool,
+    divider: u8,
+    decay: u8,
+    pub looping: bool,
+    pub constant: bool,
+    // Both the constant volume and the divider period
+    pub volume: u8,
+}
+
+impl Envelope {
+    // --LC VVVV, the L bit doubles as the length counter halt flag
+    pub fn write_control(&mut self, value: u8) {
+        self.looping = value & 0x20 != 0;
+        self.constant = value & 0x10 != 0;
+        self.volume = value & 0x0F;
+    }
+
+    pub fn restart(&mut self) {
+        self.start = true;
+    }
+
+    // Clocked every quarter frame
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+            return;
+        }
+
+        if self.divider > 0 {
+            self.divider -= 1;
+            return;
+        }
+
+        self.divider = self.volume;
+        if self.decay > 0 {
+            self.decay -= 1;
+        } else if self.looping {
+            self.decay = 15;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.constant {
+            self.volume
+        } else {
+            self.decay
+        }
+    }
+}
@@ -0,0 +1,27 @@
+/*
+Sound chips that live on the cartridge, the console mixes their output in after its own channels.
+They're all clocked on the CPU clock and output in the same units as mixer::mix, relative to
+a full volume APU pulse since that's how their loudness is usually measured. Unless a chip
+says otherwise, one of its channels at full volume is about as loud as an APU pulse.
+*/
+
+pub mod fds;
+pub mod mmc5;
+pub mod namco_163;
+pub mod sunsoft_5b;
+pub mod vrc6;
+pub mod vrc7;
+
+// What each sound chip on the cartridge is outputting, each one gets its own mixer channel
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ExpansionOutput {
+    pub vrc6: f32,
+    pub vrc7: f32,
+    pub fds: f32,
+    pub mmc5: f32,
+    pub namco_163: f32,
+    pub sunsoft_5b: f32,
+}
+
+// mixer::mix_pulse(15.0, 0.0)
+pub const APU_PULSE_LEVEL: f32 = 0.1494;
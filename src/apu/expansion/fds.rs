@@ -0,0 +1,241 @@
+use crate::apu::expansion::APU_PULSE_LEVEL;
+
+// Master volume in 30ths, $4089 picks 2/2, 2/3, 2/4 or 2/5
+const MASTER_VOLUME: [f32; 4] = [30.0, 20.0, 15.0, 12.0];
+
+// The output is 6 bit samples times a gain of up to 32, the FDS is twice as loud as the others
+const LEVEL_PER_STEP: f32 = APU_PULSE_LEVEL * 2.0 / (63.0 * 32.0 * 30.0);
+
+// How the modulation counter moves for each 3 bit entry of the modulation table,
+// None resets it to 0
+const MODULATION_STEPS: [Option<i8>; 8] = [
+    Some(0),
+    Some(1),
+    Some(2),
+    Some(4),
+    None,
+    Some(-4),
+    Some(-2),
+    Some(-1),
+];
+
+#[derive(Default)]
+struct FdsEnvelope {
+    disabled: bool,
+    increase: bool,
+    speed: u8,
+    gain: u8,
+    timer: u32,
+}
+
+impl FdsEnvelope {
+    // $4080 and $4084
+    fn write(&mut self, value: u8) {
+        self.disabled = value & 0x80 != 0;
+        self.increase = value & 0x40 != 0;
+        self.speed = value & 0x3F;
+        // With the envelope off the speed bits are the gain itself
+        if self.disabled {
+            self.gain = value & 0x3F;
+        }
+    }
+
+    fn clock(&mut self, master_speed: u8) {
+        if self.disabled {
+            return;
+        }
+
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = 8 * (master_speed as u32 + 1) * (self.speed as u32 + 1);
+
+        if self.increase && self.gain < 32 {
+            self.gain += 1;
+        } else if !self.increase && self.gain > 0 {
+            self.gain -= 1;
+        }
+    }
+}
+
+// The Famicom Disk System's sound, a single 64 step wavetable whose pitch can be bent by
+// a second table through the modulation unit
+pub struct FdsAudio {
+    wave_table: [u8; 64],
+    // Wave RAM is only writable while this is set, and the output holds meanwhile
+    wave_write: bool,
+    wave_halt: bool,
+    wave_frequency: u16,
+    wave_accumulator: u32,
+    wave_position: u8,
+    volume: FdsEnvelope,
+
+    modulation_table: [u8; 64],
+    modulation_halt: bool,
+    modulation_frequency: u16,
+    modulation_accumulator: u32,
+    modulation_position: u8,
+    // 7 bit signed
+    modulation_counter: i8,
+    modulation: FdsEnvelope,
+
+    envelopes_halt: bool,
+    master_speed: u8,
+    master_volume: u8,
+    output: u8,
+}
+
+impl FdsAudio {
+    pub fn new() -> Self {
+        FdsAudio {
+            wave_table: [0; 64],
+            wave_write: false,
+            wave_halt: true,
+            wave_frequency: 0,
+            wave_accumulator: 0,
+            wave_position: 0,
+            volume: FdsEnvelope::default(),
+            modulation_table: [0; 64],
+            modulation_halt: true,
+            modulation_frequency: 0,
+            modulation_accumulator: 0,
+            modulation_position: 0,
+            modulation_counter: 0,
+            modulation: FdsEnvelope::default(),
+            envelopes_halt: false,
+            master_speed: 0xE8,
+            master_volume: 0,
+            output: 0,
+        }
+    }
+
+    // Takes the register as $4040-$408A
+    pub fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4040..=0x407F if self.wave_write => {
+                self.wave_table[(addr - 0x4040) as usize] = value & 0x3F
+            }
+            0x4080 => self.volume.write(value),
+            0x4082 => self.wave_frequency = (self.wave_frequency & 0xF00) | value as u16,
+            0x4083 => {
+                self.wave_frequency = (self.wave_frequency & 0xFF) | (((value & 0x0F) as u16) << 8);
+                self.envelopes_halt = value & 0x40 != 0;
+                self.wave_halt = value & 0x80 != 0;
+                if self.wave_halt {
+                    self.wave_accumulator = 0;
+                    self.wave_position = 0;
+                }
+            }
+            0x4084 => self.modulation.write(value),
+            0x4085 => self.modulation_counter = ((value << 1) as i8) >> 1,
+            0x4086 => {
+                self.modulation_frequency = (self.modulation_frequency & 0xF00) | value as u16
+            }
+            0x4087 => {
+                self.modulation_frequency =
+                    (self.modulation_frequency & 0xFF) | (((value & 0x0F) as u16) << 8);
+                self.modulation_halt = value & 0x80 != 0;
+                if self.modulation_halt {
+                    self.modulation_accumulator = 0;
+                }
+            }
+            // Each write fills two entries, only while modulation is halted
+            0x4088 if self.modulation_halt => {
+                for _ in 0..2 {
+                    self.modulation_table[self.modulation_position as usize] = value & 7;
+                    self.modulation_position = (self.modulation_position + 1) & 0x3F;
+                }
+            }
+            0x4089 => {
+                self.wave_write = value & 0x80 != 0;
+                self.master_volume = value & 3;
+            }
+            0x408A => self.master_speed = value,
+            _ => (),
+        }
+    }
+
+    pub fn read(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x4040..=0x407F => Some(self.wave_table[(addr - 0x4040) as usize]),
+            0x4090 => Some(self.volume.gain),
+            0x4092 => Some(self.modulation.gain),
+            _ => None,
+        }
+    }
+
+    // The modulation counter times its gain bends the wave's pitch, with the hardware's rounding
+    fn modulated_frequency(&self) -> u32 {
+        let pitch = self.wave_frequency as i32;
+        let counter = self.modulation_counter as i32;
+
+        let mut temp = counter * self.modulation.gain as i32;
+        let remainder = temp & 0x0F;
+        temp >>= 4;
+        if remainder > 0 && temp & 0x80 == 0 {
+            temp += if counter < 0 { -1 } else { 2 };
+        }
+        if temp >= 192 {
+            temp -= 256;
+        } else if temp < -64 {
+            temp += 256;
+        }
+
+        temp *= pitch;
+        let remainder = temp & 0x3F;
+        temp >>= 6;
+        if remainder >= 32 {
+            temp += 1;
+        }
+
+        (pitch + temp).max(0) as u32
+    }
+
+    fn clock_modulation(&mut self) {
+        if self.modulation_halt {
+            return;
+        }
+
+        self.modulation_accumulator += self.modulation_frequency as u32;
+        if self.modulation_accumulator < 0x10000 {
+            return;
+        }
+        self.modulation_accumulator &= 0xFFFF;
+
+        let step =
+            MODULATION_STEPS[self.modulation_table[self.modulation_position as usize] as usize];
+        self.modulation_counter = match step {
+            // Wraps around in 7 bits
+            Some(step) => (self.modulation_counter.wrapping_add(step) << 1) >> 1,
+            None => 0,
+        };
+        self.modulation_position = (self.modulation_position + 1) & 0x3F;
+    }
+
+    pub fn clock(&mut self) {
+        if !self.envelopes_halt && !self.wave_halt && self.master_speed != 0 {
+            self.volume.clock(self.master_speed);
+            self.modulation.clock(self.master_speed);
+        }
+
+        self.clock_modulation();
+
+        if !self.wave_halt {
+            self.wave_accumulator += self.modulated_frequency();
+            if self.wave_accumulator >= 0x10000 {
+                self.wave_accumulator &= 0xFFFF;
+                self.wave_position = (self.wave_position + 1) & 0x3F;
+            }
+        }
+
+        if !self.wave_write {
+            self.output = self.wave_table[self.wave_position as usize];
+        }
+    }
+
+    pub fn output(&self) -> f32 {
+        let gain = self.volume.gain.min(32) as f32;
+        self.output as f32 * gain * MASTER_VOLUME[self.master_volume as usize] * LEVEL_PER_STEP
+    }
+}
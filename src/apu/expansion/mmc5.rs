@@ -0,0 +1,69 @@
+use crate::apu::mixer;
+use crate::apu::pulse::{Pulse, PulseChannel};
+
+// The MMC5 has no frame counter, its envelopes and length counters run at a fixed 240Hz
+const FRAME_PERIOD: u16 = 7457;
+
+// The MMC5's two extra pulses and its 8 bit PCM channel
+pub struct Mmc5Audio {
+    pulse1: Pulse,
+    pulse2: Pulse,
+    pcm: u8,
+    frame_timer: u16,
+    odd_cycle: bool,
+}
+
+impl Mmc5Audio {
+    pub fn new() -> Self {
+        Mmc5Audio {
+            pulse1: Pulse::new(PulseChannel::Mmc5),
+            pulse2: Pulse::new(PulseChannel::Mmc5),
+            pcm: 0,
+            frame_timer: 0,
+            odd_cycle: false,
+        }
+    }
+
+    // Takes the register as $5000-$5015
+    pub fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x5000..=0x5003 => self.pulse1.write(addr & 3, value),
+            0x5004..=0x5007 => self.pulse2.write(addr & 3, value),
+            // Only write mode is supported, read mode samples come from PRG reads
+            0x5011 if value != 0 => self.pcm = value,
+            0x5015 => {
+                self.pulse1.length_counter.set_enabled(value & 1 != 0);
+                self.pulse2.length_counter.set_enabled(value & 2 != 0);
+            }
+            _ => (),
+        }
+    }
+
+    pub fn read_status(&self) -> u8 {
+        (self.pulse1.length_counter.is_active() as u8)
+            | (self.pulse2.length_counter.is_active() as u8) << 1
+    }
+
+    pub fn clock(&mut self) {
+        self.frame_timer += 1;
+        if self.frame_timer == FRAME_PERIOD {
+            self.frame_timer = 0;
+            for pulse in [&mut self.pulse1, &mut self.pulse2].iter_mut() {
+                pulse.envelope.clock();
+                pulse.length_counter.clock();
+            }
+        }
+
+        if self.odd_cycle {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+        self.odd_cycle = !self.odd_cycle;
+    }
+
+    // The pulses go through the same kind of DAC as the APU's, PCM as loud as a full DMC
+    pub fn output(&self) -> f32 {
+        mixer::mix_pulse(self.pulse1.output() as f32, self.pulse2.output() as f32)
+            + mixer::mix_tnd(0.0, 0.0, 127.0) * self.pcm as f32 / 255.0
+    }
+}
@@ -0,0 +1,126 @@
+use crate::apu::expansion::APU_PULSE_LEVEL;
+
+// Each channel gets updated once every 15 CPU cycles, in turn
+const CYCLES_PER_CHANNEL: u8 = 15;
+const CHANNEL_REGISTERS: usize = 0x40;
+
+// 4 bit samples centered on 8, times a 4 bit volume
+const LEVEL_PER_STEP: f32 = APU_PULSE_LEVEL / 120.0;
+
+// The Namco 163's wavetable sound. Its 128 bytes of RAM hold both the 4 bit samples and,
+// from $40 up, the registers of up to 8 channels. Only one channel is output at a time,
+// we average them instead of reproducing the multiplexing whine
+pub struct Namco163Audio {
+    ram: [u8; 0x80],
+    address: u8,
+    auto_increment: bool,
+    divider: u8,
+    // Channel 7 is always updated, fewer active channels means faster updates for the rest
+    current_channel: usize,
+    outputs: [f32; 8],
+    disabled: bool,
+}
+
+impl Namco163Audio {
+    pub fn new() -> Self {
+        Namco163Audio {
+            ram: [0; 0x80],
+            address: 0,
+            auto_increment: false,
+            divider: 0,
+            current_channel: 7,
+            outputs: [0.0; 8],
+            disabled: false,
+        }
+    }
+
+    // $F800, bit 7 turns on auto increment
+    pub fn write_address(&mut self, value: u8) {
+        self.address = value & 0x7F;
+        self.auto_increment = value & 0x80 != 0;
+    }
+
+    fn step_address(&mut self) {
+        if self.auto_increment {
+            self.address = (self.address + 1) & 0x7F;
+        }
+    }
+
+    // $4800
+    pub fn write_data(&mut self, value: u8) {
+        self.ram[self.address as usize] = value;
+        self.step_address();
+    }
+
+    pub fn read_data(&mut self) -> u8 {
+        let value = self.ram[self.address as usize];
+        self.step_address();
+        value
+    }
+
+    pub fn set_disabled(&mut self, disabled: bool) {
+        self.disabled = disabled;
+    }
+
+    fn active_channels(&self) -> usize {
+        ((self.ram[0x7F] >> 4) & 7) as usize + 1
+    }
+
+    // 4 bit samples packed low nibble first
+    fn sample(&self, index: u8) -> u8 {
+        let byte = self.ram[(index >> 1) as usize];
+        if index & 1 == 0 {
+            byte & 0x0F
+        } else {
+            byte >> 4
+        }
+    }
+
+    fn update_channel(&mut self, channel: usize) {
+        let base = CHANNEL_REGISTERS + channel * 8;
+        let regs = &self.ram[base..base + 8];
+
+        let frequency = regs[0] as u32 | (regs[2] as u32) << 8 | ((regs[4] & 3) as u32) << 16;
+        let phase = regs[1] as u32 | (regs[3] as u32) << 8 | (regs[5] as u32) << 16;
+        let length = (256 - (regs[4] & 0xFC) as u32) << 16;
+        let wave_address = regs[6];
+        let volume = regs[7] & 0x0F;
+
+        let phase = (phase + frequency) % length;
+        self.ram[base + 1] = phase as u8;
+        self.ram[base + 3] = (phase >> 8) as u8;
+        self.ram[base + 5] = (phase >> 16) as u8;
+
+        let sample = self.sample(wave_address.wrapping_add((phase >> 16) as u8));
+        self.outputs[channel] = (sample as f32 - 8.0) * volume as f32;
+    }
+
+    pub fn clock(&mut self) {
+        self.divider += 1;
+        if self.divider < CYCLES_PER_CHANNEL {
+            return;
+        }
+        self.divider = 0;
+
+        let first_channel = 8 - self.active_channels();
+        if self.current_channel < first_channel {
+            self.current_channel = 7;
+        }
+        self.update_channel(self.current_channel);
+        self.current_channel = if self.current_channel == first_channel {
+            7
+        } else {
+            self.current_channel - 1
+        };
+    }
+
+    pub fn output(&self) -> f32 {
+        if self.disabled {
+            return 0.0;
+        }
+
+        let active = self.active_channels();
+        let sum: f32 = self.outputs[8 - active..].iter().sum();
+        sum / active as f32 * LEVEL_PER_STEP
+    }
+}
@@ -0,0 +1,214 @@
+use crate::apu::expansion::APU_PULSE_LEVEL;
+use bitflags::bitflags;
+
+// Everything inside runs off a divider of 16 CPU cycles
+const PRESCALER: u8 = 16;
+
+#[derive(Default, Clone, Copy)]
+struct ToneChannel {
+    period: u16,
+    counter: u16,
+    output: bool,
+    // Bit 4 of the volume register, use the envelope instead of the fixed volume
+    use_envelope: bool,
+    volume: u8,
+}
+
+impl ToneChannel {
+    fn clock(&mut self) {
+        self.counter += 1;
+        if self.counter >= self.period {
+            self.counter = 0;
+            self.output = !self.output;
+        }
+    }
+}
+
+bitflags! {
+    #[derive(Default)]
+    struct EnvelopeShape: u8 {
+        const HOLD =        1;
+        const ALTERNATE =   2;
+        const ATTACK =      4;
+        const CONTINUE =    8;
+    }
+}
+
+// Sunsoft's AY-3-8910 clone, 3 square channels with a shared noise generator and envelope
+pub struct Sunsoft5bAudio {
+    // Selected through $C000, written through $E000
+    register: u8,
+    tones: [ToneChannel; 3],
+    // Bits 0-2 disable the tone of a channel, bits 3-5 its noise
+    mixer: u8,
+
+    noise_period: u8,
+    noise_counter: u8,
+    // 17 bit linear feedback shift register
+    noise_shift: u32,
+
+    envelope_period: u16,
+    envelope_counter: u16,
+    envelope_shape: EnvelopeShape,
+    envelope_step: u8,
+    envelope_attack: bool,
+    envelope_holding: bool,
+
+    prescaler: u8,
+    // Amplitude of each 5 bit level, the chip's volume steps are logarithmic
+    levels: [f32; 32],
+}
+
+impl Sunsoft5bAudio {
+    pub fn new() -> Self {
+        let mut levels = [0.0; 32];
+        // 1.5dB per step down from the loudest
+        for (level, amplitude) in levels.iter_mut().enumerate().skip(1) {
+            *amplitude = APU_PULSE_LEVEL * 10f32.powf((level as f32 - 31.0) * 1.5 / 20.0);
+        }
+
+        Sunsoft5bAudio {
+            register: 0,
+            tones: [ToneChannel::default(); 3],
+            mixer: 0,
+            noise_period: 0,
+            noise_counter: 0,
+            noise_shift: 1,
+            envelope_period: 0,
+            envelope_counter: 0,
+            envelope_shape: EnvelopeShape::empty(),
+            envelope_step: 0,
+            envelope_attack: false,
+            envelope_holding: false,
+            prescaler: 0,
+            levels,
+        }
+    }
+
+    pub fn write_address(&mut self, value: u8) {
+        self.register = value;
+    }
+
+    pub fn write_data(&mut self, value: u8) {
+        match self.register {
+            0x00 | 0x02 | 0x04 => {
+                let tone = &mut self.tones[(self.register / 2) as usize];
+                tone.period = (tone.period & 0xF00) | value as u16;
+            }
+            0x01 | 0x03 | 0x05 => {
+                let tone = &mut self.tones[(self.register / 2) as usize];
+                tone.period = (tone.period & 0xFF) | (((value & 0x0F) as u16) << 8);
+            }
+            0x06 => self.noise_period = value & 0x1F,
+            0x07 => self.mixer = value,
+            0x08..=0x0A => {
+                let tone = &mut self.tones[(self.register - 8) as usize];
+                tone.use_envelope = value & 0x10 != 0;
+                tone.volume = value & 0x0F;
+            }
+            0x0B => self.envelope_period = (self.envelope_period & 0xFF00) | value as u16,
+            0x0C => self.envelope_period = (self.envelope_period & 0xFF) | ((value as u16) << 8),
+            0x0D => {
+                self.envelope_shape = EnvelopeShape::from_bits_truncate(value);
+                self.envelope_attack = self.envelope_shape.contains(EnvelopeShape::ATTACK);
+                self.envelope_step = 0;
+                self.envelope_counter = 0;
+                self.envelope_holding = false;
+            }
+            // Registers $E and $F are I/O ports, and anything above $F doesn't select a register
+            _ => (),
+        }
+    }
+
+    fn clock_noise(&mut self) {
+        // Noise steps at half the rate of the tone counters
+        self.noise_counter += 1;
+        if self.noise_counter >= self.noise_period.max(1) * 2 {
+            self.noise_counter = 0;
+            let feedback = (self.noise_shift ^ (self.noise_shift >> 3)) & 1;
+            self.noise_shift = (self.noise_shift >> 1) | (feedback << 16);
+        }
+    }
+
+    fn clock_envelope(&mut self) {
+        self.envelope_counter += 1;
+        if self.envelope_counter < self.envelope_period.max(1) {
+            return;
+        }
+        self.envelope_counter = 0;
+
+        if self.envelope_holding {
+            return;
+        }
+
+        self.envelope_step += 1;
+        if self.envelope_step > 31 {
+            let shape = self.envelope_shape;
+            if !shape.contains(EnvelopeShape::CONTINUE) {
+                // One ramp and then silence
+                self.envelope_holding = true;
+                self.envelope_attack = false;
+                self.envelope_step = 31;
+            } else if shape.contains(EnvelopeShape::HOLD) {
+                self.envelope_holding = true;
+                if shape.contains(EnvelopeShape::ALTERNATE) {
+                    self.envelope_attack = !self.envelope_attack;
+                }
+                self.envelope_step = 31;
+            } else {
+                if shape.contains(EnvelopeShape::ALTERNATE) {
+                    self.envelope_attack = !self.envelope_attack;
+                }
+                self.envelope_step = 0;
+            }
+        }
+    }
+
+    fn envelope_level(&self) -> u8 {
+        if self.envelope_attack {
+            self.envelope_step
+        } else {
+            31 - self.envelope_step
+        }
+    }
+
+    pub fn clock(&mut self) {
+        self.prescaler += 1;
+        if self.prescaler < PRESCALER {
+            return;
+        }
+        self.prescaler = 0;
+
+        for tone in self.tones.iter_mut() {
+            tone.clock();
+        }
+        self.clock_noise();
+        self.clock_envelope();
+    }
+
+    pub fn output(&self) -> f32 {
+        let noise = self.noise_shift & 1 != 0;
+
+        self.tones
+            .iter()
+            .enumerate()
+            .map(|(channel, tone)| {
+                let tone_disabled = self.mixer & (1 << channel) != 0;
+                let noise_disabled = self.mixer & (8 << channel) != 0;
+                if !((tone.output || tone_disabled) && (noise || noise_disabled)) {
+                    return 0.0;
+                }
+
+                // Fixed volumes use every other step of the envelope's 5 bit scale
+                let level = if tone.use_envelope {
+                    self.envelope_level()
+                } else if tone.volume == 0 {
+                    0
+                } else {
+                    tone.volume * 2 + 1
+                };
+                self.levels[level as usize]
+            })
+            .sum()
+    }
+}
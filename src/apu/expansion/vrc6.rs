@@ -0,0 +1,162 @@
+use crate::apu::expansion::APU_PULSE_LEVEL;
+
+// Pulses have 4 bit volumes, the saw's 5 bit output uses the same steps
+const LEVEL_PER_STEP: f32 = APU_PULSE_LEVEL / 15.0;
+
+#[derive(Default)]
+struct Vrc6Pulse {
+    // Ignores the duty cycle and outputs the volume all the time
+    digitized: bool,
+    // The output is high for steps 0 to duty of 16
+    duty: u8,
+    volume: u8,
+    enabled: bool,
+    step: u8,
+    timer: u16,
+    timer_period: u16,
+}
+
+impl Vrc6Pulse {
+    fn write(&mut self, reg: u16, value: u8) {
+        match reg {
+            0 => {
+                self.digitized = value & 0x80 != 0;
+                self.duty = (value >> 4) & 7;
+                self.volume = value & 0x0F;
+            }
+            1 => self.timer_period = (self.timer_period & 0xF00) | value as u16,
+            2 => {
+                self.timer_period = (self.timer_period & 0xFF) | (((value & 0x0F) as u16) << 8);
+                self.enabled = value & 0x80 != 0;
+                if !self.enabled {
+                    self.step = 0;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn clock(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.timer == 0 {
+            self.timer = self.timer_period >> shift;
+            self.step = (self.step + 1) & 0x0F;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.enabled && (self.digitized || self.step <= self.duty) {
+            self.volume
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Default)]
+struct Vrc6Saw {
+    rate: u8,
+    accumulator: u8,
+    // The accumulator adds on every other step and resets after the 14th
+    step: u8,
+    enabled: bool,
+    timer: u16,
+    timer_period: u16,
+}
+
+impl Vrc6Saw {
+    fn write(&mut self, reg: u16, value: u8) {
+        match reg {
+            0 => self.rate = value & 0x3F,
+            1 => self.timer_period = (self.timer_period & 0xF00) | value as u16,
+            2 => {
+                self.timer_period = (self.timer_period & 0xFF) | (((value & 0x0F) as u16) << 8);
+                self.enabled = value & 0x80 != 0;
+                if !self.enabled {
+                    self.accumulator = 0;
+                    self.step = 0;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn clock(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.timer_period >> shift;
+
+        self.step += 1;
+        if self.step == 14 {
+            self.step = 0;
+            self.accumulator = 0;
+        } else if self.step & 1 == 0 {
+            self.accumulator = self.accumulator.wrapping_add(self.rate);
+        }
+    }
+
+    // The top 5 bits of the accumulator
+    fn output(&self) -> u8 {
+        self.accumulator >> 3
+    }
+}
+
+// Konami's VRC6 sound, two pulses with 8 duty cycles and a sawtooth
+#[derive(Default)]
+pub struct Vrc6Audio {
+    pulse1: Vrc6Pulse,
+    pulse2: Vrc6Pulse,
+    saw: Vrc6Saw,
+    halt: bool,
+    // Speeds all the timers up by dropping their low bits
+    shift: u8,
+}
+
+impl Vrc6Audio {
+    // Takes the register as $9000-$B003 on mapper 24 wiring
+    pub fn write(&mut self, addr: u16, value: u8) {
+        let reg = addr & 3;
+        match addr & 0xF000 {
+            0x9000 if reg == 3 => {
+                self.halt = value & 1 != 0;
+                self.shift = if value & 4 != 0 {
+                    8
+                } else if value & 2 != 0 {
+                    4
+                } else {
+                    0
+                };
+            }
+            0x9000 => self.pulse1.write(reg, value),
+            0xA000 => self.pulse2.write(reg, value),
+            0xB000 => self.saw.write(reg, value),
+            _ => (),
+        }
+    }
+
+    pub fn clock(&mut self) {
+        if self.halt {
+            return;
+        }
+        self.pulse1.clock(self.shift);
+        self.pulse2.clock(self.shift);
+        self.saw.clock(self.shift);
+    }
+
+    // All three are summed linearly
+    pub fn output(&self) -> f32 {
+        let sum = self.pulse1.output() + self.pulse2.output() + self.saw.output();
+        sum as f32 * LEVEL_PER_STEP
+    }
+}
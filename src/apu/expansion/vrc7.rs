@@ -0,0 +1,428 @@
+use crate::apu::expansion::APU_PULSE_LEVEL;
+use std::f32::consts::TAU;
+
+/*
+The VRC7's sound is a cut down YM2413 (OPLL): 6 FM channels of 2 operators each, a modulator
+feeding into a carrier, no rhythm mode. Instruments are picked from a ROM of 15 presets, plus
+one the game can define through registers $00-$07.
+
+The real chip works with log-sin tables and integer envelopes, this approximates it in floats.
+Attenuations are in dB, phases in fractions of a cycle.
+*/
+
+// The chip makes a sample every 72 of its clocks, which run at twice the CPU clock
+const CYCLES_PER_SAMPLE: u8 = 36;
+const SAMPLE_RATE: f32 = 49_716.0;
+
+const CHANNEL_COUNT: usize = 6;
+// Past this the envelope is silent
+const MAX_ATTENUATION: f32 = 48.0;
+
+// Peak of one channel at full volume
+const CHANNEL_LEVEL: f32 = APU_PULSE_LEVEL;
+
+// Frequency multipliers times 2, the first one is a half
+const MULTIPLIER_X2: [u8; 16] = [1, 2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 20, 24, 24, 30, 30];
+
+// Key scale level at 6dB per octave for the top 4 bits of the F-number in the highest octave
+const KEY_SCALE_LEVEL: [f32; 16] = [
+    0.0, 18.0, 24.0, 27.75, 30.0, 32.25, 33.75, 35.25, 36.0, 37.5, 38.25, 39.0, 39.75, 40.5, 41.25,
+    42.0,
+];
+
+// Tremolo and vibrato are shared by all channels
+const TREMOLO_RATE: f32 = 3.7;
+const TREMOLO_DEPTH: f32 = 4.8;
+const VIBRATO_RATE: f32 = 6.4;
+// About 7 cents each way
+const VIBRATO_DEPTH: f32 = 0.004;
+
+// How far the modulator at full volume pushes the carrier's phase, in cycles
+const MODULATION_DEPTH: f32 = 2.0;
+
+// Time for a full 48dB decay and a full attack at the slowest rate, each 4 rates are twice as fast
+const DECAY_TIME: f32 = 20.0;
+const ATTACK_TIME: f32 = 2.826;
+
+// The VRC7's built in instruments, in the same layout as registers $00-$07
+const PATCHES: [[u8; 8]; 15] = [
+    [0x03, 0x21, 0x05, 0x06, 0xE8, 0x81, 0x42, 0x27], // Buzzy bell
+    [0x13, 0x41, 0x14, 0x0D, 0xD8, 0xF6, 0x23, 0x12], // Guitar
+    [0x11, 0x11, 0x08, 0x08, 0xFA, 0xB2, 0x20, 0x12], // Wurly
+    [0x31, 0x61, 0x0C, 0x07, 0xA8, 0x64, 0x61, 0x27], // Flute
+    [0x32, 0x21, 0x1E, 0x06, 0xE1, 0x76, 0x01, 0x28], // Clarinet
+    [0x02, 0x01, 0x06, 0x00, 0xA3, 0xE2, 0xF4, 0xF4], // Synth
+    [0x21, 0x61, 0x1D, 0x07, 0x82, 0x81, 0x11, 0x07], // Trumpet
+    [0x23, 0x21, 0x22, 0x17, 0xA2, 0x72, 0x01, 0x17], // Organ
+    [0x35, 0x11, 0x25, 0x00, 0x40, 0x73, 0x72, 0x01], // Bells
+    [0xB5, 0x01, 0x0F, 0x0F, 0xA8, 0xA5, 0x51, 0x02], // Vibes
+    [0x17, 0xC1, 0x24, 0x07, 0xF8, 0xF8, 0x22, 0x12], // Vibraphone
+    [0x71, 0x23, 0x11, 0x06, 0x65, 0x74, 0x18, 0x16], // Tutti
+    [0x01, 0x02, 0xD3, 0x05, 0xC9, 0x95, 0x03, 0x02], // Fretless
+    [0x61, 0x63, 0x0C, 0x00, 0x94, 0xC0, 0x33, 0xF6], // Synth bass
+    [0x21, 0x72, 0x0D, 0x00, 0xC1, 0xD5, 0x56, 0x06], // Sweep
+];
+
+#[derive(Clone, Copy, Default)]
+struct OperatorPatch {
+    tremolo: bool,
+    vibrato: bool,
+    // Holds at the sustain level until key off, otherwise keeps decaying at the release rate
+    sustained: bool,
+    key_scale_rate: bool,
+    multiplier: u8,
+    key_scale_level: u8,
+    // Only half of the sine is output
+    rectified: bool,
+    attack: u8,
+    decay: u8,
+    sustain_level: u8,
+    release: u8,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Patch {
+    modulator: OperatorPatch,
+    carrier: OperatorPatch,
+    // Modulator attenuation in 0.75dB steps
+    total_level: u8,
+    feedback: u8,
+}
+
+impl Patch {
+    fn decode(bytes: &[u8; 8]) -> Self {
+        let operator = |flags: u8, ksl: u8, rectified: bool, rates: u8, sustain: u8| OperatorPatch {
+            tremolo: flags & 0x80 != 0,
+            vibrato: flags & 0x40 != 0,
+            sustained: flags & 0x20 != 0,
+            key_scale_rate: flags & 0x10 != 0,
+            multiplier: flags & 0x0F,
+            key_scale_level: ksl >> 6,
+            rectified,
+            attack: rates >> 4,
+            decay: rates & 0x0F,
+            sustain_level: sustain >> 4,
+            release: sustain & 0x0F,
+        };
+
+        Patch {
+            modulator: operator(bytes[0], bytes[2], bytes[3] & 0x08 != 0, bytes[4], bytes[6]),
+            carrier: operator(bytes[1], bytes[3], bytes[3] & 0x10 != 0, bytes[5], bytes[7]),
+            total_level: bytes[2] & 0x3F,
+            feedback: bytes[3] & 7,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum EnvelopeState {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    Off,
+}
+
+#[derive(Clone, Copy)]
+struct Operator {
+    phase: f32,
+    state: EnvelopeState,
+    attenuation: f32,
+    // The last two outputs, modulators feed them back into themselves
+    output: [f32; 2],
+}
+
+impl Default for Operator {
+    fn default() -> Self {
+        Operator {
+            phase: 0.0,
+            state: EnvelopeState::Off,
+            attenuation: MAX_ATTENUATION,
+            output: [0.0; 2],
+        }
+    }
+}
+
+impl Operator {
+    fn key_on(&mut self) {
+        self.phase = 0.0;
+        self.state = EnvelopeState::Attack;
+    }
+
+    fn key_off(&mut self) {
+        if self.state != EnvelopeState::Off {
+            self.state = EnvelopeState::Release;
+        }
+    }
+
+    // Rates go from 0 to 63, 4 per register step plus the key scale offset
+    fn effective_rate(rate: u8, key_scale_offset: u8) -> u8 {
+        if rate == 0 {
+            0
+        } else {
+            (rate * 4 + key_scale_offset).min(63)
+        }
+    }
+
+    fn decay_step(rate: u8) -> f32 {
+        if rate == 0 {
+            return 0.0;
+        }
+        let time = DECAY_TIME * 2f32.powf(-(rate as f32 - 4.0) / 4.0);
+        MAX_ATTENUATION / (time * SAMPLE_RATE)
+    }
+
+    fn clock_envelope(&mut self, patch: &OperatorPatch, key_scale_offset: u8, channel_sustain: bool) {
+        let offset = if patch.key_scale_rate {
+            key_scale_offset
+        } else {
+            key_scale_offset >> 2
+        };
+
+        match self.state {
+            EnvelopeState::Attack => {
+                let rate = Operator::effective_rate(patch.attack, offset);
+                if rate >= 60 {
+                    self.attenuation = 0.0;
+                } else if rate > 0 {
+                    // The attack is exponential, it slows down as it gets louder
+                    let time = ATTACK_TIME * 2f32.powf(-(rate as f32 - 4.0) / 4.0);
+                    let factor = (0.1f32 / MAX_ATTENUATION).powf(1.0 / (time * SAMPLE_RATE));
+                    self.attenuation *= factor;
+                    if self.attenuation < 0.1 {
+                        self.attenuation = 0.0;
+                    }
+                }
+                if self.attenuation == 0.0 {
+                    self.state = EnvelopeState::Decay;
+                }
+            }
+            EnvelopeState::Decay => {
+                let sustain_level = patch.sustain_level as f32 * 3.0;
+                self.attenuation +=
+                    Operator::decay_step(Operator::effective_rate(patch.decay, offset));
+                if self.attenuation >= sustain_level {
+                    self.attenuation = sustain_level;
+                    self.state = EnvelopeState::Sustain;
+                }
+            }
+            EnvelopeState::Sustain => {
+                if !patch.sustained {
+                    self.attenuation +=
+                        Operator::decay_step(Operator::effective_rate(patch.release, offset));
+                }
+            }
+            EnvelopeState::Release => {
+                let rate = if channel_sustain {
+                    5
+                } else if patch.sustained {
+                    patch.release
+                } else {
+                    7
+                };
+                self.attenuation += Operator::decay_step(Operator::effective_rate(rate, offset));
+            }
+            EnvelopeState::Off => (),
+        }
+
+        if self.attenuation >= MAX_ATTENUATION {
+            self.attenuation = MAX_ATTENUATION;
+            if self.state != EnvelopeState::Attack {
+                self.state = EnvelopeState::Off;
+            }
+        }
+    }
+
+    // Takes the attenuation on top of the envelope and the phase offset from modulation
+    fn output(&mut self, rectified: bool, attenuation: f32, modulation: f32) -> f32 {
+        let attenuation = self.attenuation + attenuation;
+        let sine = ((self.phase + modulation) * TAU).sin();
+        let output = if attenuation >= MAX_ATTENUATION || (rectified && sine < 0.0) {
+            0.0
+        } else {
+            sine * 10f32.powf(-attenuation / 20.0)
+        };
+
+        self.output = [output, self.output[0]];
+        output
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct FmChannel {
+    // 9 bit frequency number and 3 bit octave
+    f_number: u16,
+    block: u8,
+    key_on: bool,
+    // Slows down the release
+    sustain: bool,
+    instrument: u8,
+    // Carrier attenuation in 3dB steps
+    volume: u8,
+    modulator: Operator,
+    carrier: Operator,
+}
+
+impl FmChannel {
+    fn key_scale_offset(&self) -> u8 {
+        (self.block << 1) | (self.f_number >> 8) as u8
+    }
+
+    fn key_scale_level(&self, level: u8) -> f32 {
+        if level == 0 {
+            return 0.0;
+        }
+        let base = KEY_SCALE_LEVEL[(self.f_number >> 5) as usize] - 6.0 * (7 - self.block) as f32;
+        // 1.5, 3 or 6dB per octave
+        base.max(0.0) / (1 << (3 - level)) as f32
+    }
+}
+
+pub struct Vrc7Audio {
+    // Selected through $9010, written through $9030
+    register: u8,
+    custom_patch: [u8; 8],
+    patches: [Patch; 16],
+    channels: [FmChannel; CHANNEL_COUNT],
+    tremolo_phase: f32,
+    vibrato_phase: f32,
+    // The VRC7 can silence its sound through the mirroring register
+    silenced: bool,
+    divider: u8,
+    output: f32,
+}
+
+impl Vrc7Audio {
+    pub fn new() -> Self {
+        let mut patches = [Patch::default(); 16];
+        for (patch, bytes) in patches.iter_mut().skip(1).zip(PATCHES.iter()) {
+            *patch = Patch::decode(bytes);
+        }
+
+        Vrc7Audio {
+            register: 0,
+            custom_patch: [0; 8],
+            patches,
+            channels: [FmChannel::default(); CHANNEL_COUNT],
+            tremolo_phase: 0.0,
+            vibrato_phase: 0.0,
+            silenced: false,
+            divider: 0,
+            output: 0.0,
+        }
+    }
+
+    pub fn write_address(&mut self, value: u8) {
+        self.register = value;
+    }
+
+    pub fn write_data(&mut self, value: u8) {
+        let index = (self.register & 0x0F) as usize;
+        match self.register {
+            0x00..=0x07 => {
+                self.custom_patch[index] = value;
+                self.patches[0] = Patch::decode(&self.custom_patch);
+            }
+            0x10..=0x15 => {
+                let channel = &mut self.channels[index];
+                channel.f_number = (channel.f_number & 0x100) | value as u16;
+            }
+            0x20..=0x25 => {
+                let channel = &mut self.channels[index];
+                channel.f_number = (channel.f_number & 0xFF) | (((value & 1) as u16) << 8);
+                channel.block = (value >> 1) & 7;
+                channel.sustain = value & 0x20 != 0;
+
+                let key_on = value & 0x10 != 0;
+                if key_on && !channel.key_on {
+                    channel.modulator.key_on();
+                    channel.carrier.key_on();
+                } else if !key_on && channel.key_on {
+                    channel.modulator.key_off();
+                    channel.carrier.key_off();
+                }
+                channel.key_on = key_on;
+            }
+            0x30..=0x35 => {
+                let channel = &mut self.channels[index];
+                channel.instrument = value >> 4;
+                channel.volume = value & 0x0F;
+            }
+            _ => (),
+        }
+    }
+
+    pub fn set_silenced(&mut self, silenced: bool) {
+        self.silenced = silenced;
+    }
+
+    fn sample(&mut self) -> f32 {
+        self.tremolo_phase = (self.tremolo_phase + TREMOLO_RATE / SAMPLE_RATE).fract();
+        self.vibrato_phase = (self.vibrato_phase + VIBRATO_RATE / SAMPLE_RATE).fract();
+        let tremolo = TREMOLO_DEPTH * 0.5 * (1.0 - (self.tremolo_phase * TAU).cos());
+        let vibrato = 1.0 + VIBRATO_DEPTH * (self.vibrato_phase * TAU).sin();
+
+        let mut sum = 0.0;
+        for channel in self.channels.iter_mut() {
+            let patch = self.patches[channel.instrument as usize];
+            let key_scale_offset = channel.key_scale_offset();
+            let base_step = ((channel.f_number as u32) << channel.block) as f32 / (1 << 20) as f32;
+
+            let mut modulator = channel.modulator;
+            let mut carrier = channel.carrier;
+            for (operator, operator_patch) in
+                [(&mut modulator, &patch.modulator), (&mut carrier, &patch.carrier)].iter_mut()
+            {
+                let mut step = base_step * MULTIPLIER_X2[operator_patch.multiplier as usize] as f32;
+                if operator_patch.vibrato {
+                    step *= vibrato;
+                }
+                operator.phase = (operator.phase + step).fract();
+                operator.clock_envelope(operator_patch, key_scale_offset, channel.sustain);
+            }
+
+            let feedback = if patch.feedback == 0 {
+                0.0
+            } else {
+                (modulator.output[0] + modulator.output[1]) / 2.0
+                    * MODULATION_DEPTH
+                    * 2f32.powi(patch.feedback as i32 - 7)
+            };
+            let modulator_attenuation = patch.total_level as f32 * 0.75
+                + channel.key_scale_level(patch.modulator.key_scale_level)
+                + if patch.modulator.tremolo { tremolo } else { 0.0 };
+            let modulation =
+                modulator.output(patch.modulator.rectified, modulator_attenuation, feedback);
+
+            let carrier_attenuation = channel.volume as f32 * 3.0
+                + channel.key_scale_level(patch.carrier.key_scale_level)
+                + if patch.carrier.tremolo { tremolo } else { 0.0 };
+            sum += carrier.output(
+                patch.carrier.rectified,
+                carrier_attenuation,
+                modulation * MODULATION_DEPTH,
+            );
+
+            channel.modulator = modulator;
+            channel.carrier = carrier;
+        }
+
+        sum
+    }
+
+    pub fn clock(&mut self) {
+        self.divider += 1;
+        if self.divider == CYCLES_PER_SAMPLE {
+            self.divider = 0;
+            self.output = self.sample();
+        }
+    }
+
+    pub fn output(&self) -> f32 {
+        if self.silenced {
+            0.0
+        } else {
+            self.output * CHANNEL_LEVEL
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use bitflags::bitflags;
+
+bitflags! {
+    #[derive(Default)]
+    pub struct FrameSignal: u8 {
+        const QUARTER = 0b01; // Envelopes and the triangle's linear counter
+        const HALF =    0b10; // Length counters and sweeps
+    }
+}
+
+/*
+NTSC frame sequencer step timings, in CPU cycles since the last reset
+    4-step: 7457 Q, 14913 QH, 22371 Q, 29829 QH (IRQ on 29828-29830), wraps after 29830
+    5-step: 7457 Q, 14913 QH, 22371 Q, 37281 QH, wraps after 37282, never raises an IRQ
+*/
+
+#[derive(Default)]
+pub struct FrameCounter {
+    cycle: usize,
+    five_step: bool,
+    irq_inhibit: bool,
+    pub irq_flag: bool,
+    // $4017 writes land a few cycles late, (value, CPU cycle they land on)
+    pending_write: Option<(u8, usize)>,
+}
+
+impl FrameCounter {
+    // The reset happens 3 cycles after a write made on an APU cycle (an odd CPU cycle, when
+    // the pulse timers tick) and 4 after one made in between
+    pub fn write(&mut self, value: u8, cpu_cycle: usize) {
+        self.irq_inhibit = value & 0x40 != 0;
+        if self.irq_inhibit {
+            self.irq_flag = false;
+        }
+
+        let delay = if cpu_cycle & 1 == 1 { 3 } else { 4 };
+        self.pending_write = Some((value, cpu_cycle + delay));
+    }
+
+    fn set_irq(&mut self) {
+        if !self.irq_inhibit {
+            self.irq_flag = true;
+        }
+    }
+
+    // Runs one CPU cycle, returns which units should be clocked
+    pub fn clock(&mut self, cpu_cycle: usize) -> FrameSignal {
+        if let Some((value, reset_cycle)) = self.pending_write {
+            if cpu_cycle >= reset_cycle {
+                self.pending_write = None;
+                self.five_step = value & 0x80 != 0;
+                self.cycle = 0;
+                // Switching to 5-step mode clocks everything right away
+                return if self.five_step {
+                    FrameSignal::all()
+                } else {
+                    FrameSignal::empty()
+                };
+            }
+        }
+
+        self.cycle += 1;
+        match (self.cycle, self.five_step) {
+            (7457, _) | (22371, _) => FrameSignal::QUARTER,
+            (14913, _) => FrameSignal::all(),
+            (29828, false) => {
+                self.set_irq();
+                FrameSignal::empty()
+            }
+            (29829, false) => {
+                self.set_irq();
+                FrameSignal::all()
+            }
+            (29830, false) => {
+                self.set_irq();
+                self.cycle = 0;
+                FrameSignal::empty()
+            }
+            (37281, true) => FrameSignal::all(),
+            (37282, true) => {
+                self.cycle = 0;
+                FrameSignal::empty()
+            }
+            _ => FrameSignal::empty(),
+        }
+    }
+}
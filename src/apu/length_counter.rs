@@ -0,0 +1,82 @@
+const LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14,
+    12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+// Silences a channel after a set amount of half frames, unless halted
+#[derive(Default)]
+pub struct LengthCounter {
+    counter: u8,
+    enabled: bool,
+    pub halt: bool,
+}
+
+impl LengthCounter {
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.counter = 0;
+        }
+    }
+
+    // Loaded from the top 5 bits of the channel's last register
+    pub fn load(&mut self, value: u8) {
+        if self.enabled {
+            self.counter = LENGTH_TABLE[(value >> 3) as usize];
+        }
+    }
+
+    pub fn clock(&mut self) {
+        if self.counter > 0 && !self.halt {
+            self.counter -= 1;
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.counter > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn half_frames_until_silent(value: u8) -> usize {
+        let mut counter = LengthCounter::default();
+        counter.set_enabled(true);
+        counter.load(value);
+        let mut half_frames = 0;
+        while counter.is_active() {
+            counter.clock();
+            half_frames += 1;
+        }
+        half_frames
+    }
+
+    #[test]
+    fn loads_from_the_table() {
+        assert_eq!(half_frames_until_silent(0x00), 10);
+        assert_eq!(half_frames_until_silent(0x08), 254);
+        assert_eq!(half_frames_until_silent(0xF8), 30);
+    }
+
+    #[test]
+    fn ignores_loads_while_disabled() {
+        let mut counter = LengthCounter::default();
+        counter.load(0x08);
+        assert!(!counter.is_active());
+    }
+
+    #[test]
+    fn halt_keeps_the_counter() {
+        let mut counter = LengthCounter::default();
+        counter.set_enabled(true);
+        counter.load(0x18);
+        counter.halt = true;
+        counter.clock();
+        counter.clock();
+        assert!(counter.is_active());
+        counter.set_enabled(false);
+        assert!(!counter.is_active());
+    }
+}
@@ -0,0 +1,136 @@
+/*
+The NES mixes its channels through resistor DACs, which aren't linear at all.
+These are the usual approximations from nesdev, taking the raw channel levels
+(pulses 0-15, triangle 0-15, noise 0-15, DMC 0-127) and giving an output between 0 and 1.
+*/
+
+pub fn mix_pulse(pulse1: f32, pulse2: f32) -> f32 {
+    let sum = pulse1 + pulse2;
+    if sum == 0.0 {
+        0.0
+    } else {
+        95.88 / (8128.0 / sum + 100.0)
+    }
+}
+
+pub fn mix_tnd(triangle: f32, noise: f32, dmc: f32) -> f32 {
+    let sum = triangle / 8227.0 + noise / 12241.0 + dmc / 22638.0;
+    if sum == 0.0 {
+        0.0
+    } else {
+        159.79 / (1.0 / sum + 100.0)
+    }
+}
+
+pub fn mix(pulse1: f32, pulse2: f32, triangle: f32, noise: f32, dmc: f32) -> f32 {
+    mix_pulse(pulse1, pulse2) + mix_tnd(triangle, noise, dmc)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
+    Pulse1,
+    Pulse2,
+    Triangle,
+    Noise,
+    Dmc,
+    // The cartridge's sound chips come after the APU's channels
+    Vrc6,
+    Vrc7,
+    Fds,
+    Mmc5,
+    Namco163,
+    Sunsoft5b,
+}
+
+pub const CHANNELS: [Channel; 11] = [
+    Channel::Pulse1,
+    Channel::Pulse2,
+    Channel::Triangle,
+    Channel::Noise,
+    Channel::Dmc,
+    Channel::Vrc6,
+    Channel::Vrc7,
+    Channel::Fds,
+    Channel::Mmc5,
+    Channel::Namco163,
+    Channel::Sunsoft5b,
+];
+
+impl Channel {
+    pub fn from_name(name: &str) -> Option<Channel> {
+        CHANNELS
+            .iter()
+            .copied()
+            .find(|channel| channel.get_name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Channel::Pulse1 => "pulse1",
+            Channel::Pulse2 => "pulse2",
+            Channel::Triangle => "triangle",
+            Channel::Noise => "noise",
+            Channel::Dmc => "dmc",
+            Channel::Vrc6 => "vrc6",
+            Channel::Vrc7 => "vrc7",
+            Channel::Fds => "fds",
+            Channel::Mmc5 => "mmc5",
+            Channel::Namco163 => "n163",
+            Channel::Sunsoft5b => "s5b",
+        }
+    }
+}
+
+// Mute, solo and volume for every channel, volume scales the channel's share of the output
+#[derive(Debug, Clone)]
+pub struct ChannelControls {
+    volumes: [f32; CHANNELS.len()],
+    muted: [bool; CHANNELS.len()],
+    soloed: [bool; CHANNELS.len()],
+}
+
+impl Default for ChannelControls {
+    fn default() -> Self {
+        ChannelControls {
+            volumes: [1.0; CHANNELS.len()],
+            muted: [false; CHANNELS.len()],
+            soloed: [false; CHANNELS.len()],
+        }
+    }
+}
+
+impl ChannelControls {
+    pub fn set_volume(&mut self, channel: Channel, volume: f32) {
+        self.volumes[channel as usize] = volume.max(0.0);
+    }
+
+    pub fn get_volume(&self, channel: Channel) -> f32 {
+        self.volumes[channel as usize]
+    }
+
+    pub fn set_muted(&mut self, channel: Channel, muted: bool) {
+        self.muted[channel as usize] = muted;
+    }
+
+    pub fn is_muted(&self, channel: Channel) -> bool {
+        self.muted[channel as usize]
+    }
+
+    pub fn set_soloed(&mut self, channel: Channel, soloed: bool) {
+        self.soloed[channel as usize] = soloed;
+    }
+
+    pub fn is_soloed(&self, channel: Channel) -> bool {
+        self.soloed[channel as usize]
+    }
+
+    // Once anything is soloed only the soloed channels are heard, mute still wins over solo
+    pub fn get_gain(&self, channel: Channel) -> f32 {
+        let any_soloed = self.soloed.iter().any(|&soloed| soloed);
+        if self.is_muted(channel) || (any_soloed && !self.is_soloed(channel)) {
+            0.0
+        } else {
+            self.get_volume(channel)
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::apu::envelope::Envelope;
+use crate::apu::length_counter::LengthCounter;
+
+// NTSC timer periods in CPU cycles
+const PERIOD_TABLE: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+
+pub struct Noise {
+    // 15 bit linear feedback shift register
+    shift: u16,
+    // Short mode takes the feedback from bit 6 instead of 1, giving a 93 step sequence
+    short_mode: bool,
+    timer: u16,
+    timer_period: u16,
+    pub envelope: Envelope,
+    pub length_counter: LengthCounter,
+}
+
+impl Noise {
+    pub fn new() -> Self {
+        Noise {
+            shift: 1,
+            short_mode: false,
+            timer: 0,
+            timer_period: PERIOD_TABLE[0] - 1,
+            envelope: Envelope::default(),
+            length_counter: LengthCounter::default(),
+        }
+    }
+
+    // Takes the register offset inside the channel, $400C-$400F
+    pub fn write(&mut self, reg: u16, value: u8) {
+        match reg {
+            0 => {
+                self.envelope.write_control(value);
+                self.length_counter.halt = value & 0x20 != 0;
+            }
+            1 => (), // Unused
+            2 => {
+                self.short_mode = value & 0x80 != 0;
+                self.timer_period = PERIOD_TABLE[(value & 0x0F) as usize] - 1;
+            }
+            3 => {
+                self.length_counter.load(value);
+                self.envelope.restart();
+            }
+            _ => unreachable!("Noise register match failed - impossible!"),
+        }
+    }
+
+    // Clocked every CPU cycle, the period table is in CPU cycles
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+
+            let tap = if self.short_mode { 6 } else { 1 };
+            let feedback = (self.shift & 1) ^ ((self.shift >> tap) & 1);
+            self.shift = (self.shift >> 1) | (feedback << 14);
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.shift & 1 != 0 || !self.length_counter.is_active() {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use crate::apu::envelope::Envelope;
+use crate::apu::length_counter::LengthCounter;
+
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0], // 12.5%
+    [0, 1, 1, 0, 0, 0, 0, 0], // 25%
+    [0, 1, 1, 1, 1, 0, 0, 0], // 50%
+    [1, 0, 0, 1, 1, 1, 1, 1], // 25% negated
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PulseChannel {
+    One,
+    Two,
+    // The MMC5's copies, same as ours but without a sweep unit
+    Mmc5,
+}
+
+#[derive(Default)]
+struct Sweep {
+    enabled: bool,
+    period: u8,
+    negate: bool,
+    shift: u8,
+    divider: u8,
+    reload: bool,
+}
+
+pub struct Pulse {
+    channel: PulseChannel,
+    duty: u8,
+    sequence_step: usize,
+    timer: u16,
+    timer_period: u16,
+    sweep: Sweep,
+    pub envelope: Envelope,
+    pub length_counter: LengthCounter,
+}
+
+impl Pulse {
+    pub fn new(channel: PulseChannel) -> Self {
+        Pulse {
+            channel,
+            duty: 0,
+            sequence_step: 0,
+            timer: 0,
+            timer_period: 0,
+            sweep: Sweep::default(),
+            envelope: Envelope::default(),
+            length_counter: LengthCounter::default(),
+        }
+    }
+
+    // Takes the register offset inside the channel, $4000-$4003 or $4004-$4007
+    pub fn write(&mut self, reg: u16, value: u8) {
+        match reg {
+            0 => {
+                self.duty = value >> 6;
+                self.envelope.write_control(value);
+                self.length_counter.halt = value & 0x20 != 0;
+            }
+            1 if self.channel == PulseChannel::Mmc5 => (),
+            1 => {
+                self.sweep.enabled = value & 0x80 != 0;
+                self.sweep.period = (value >> 4) & 7;
+                self.sweep.negate = value & 0x08 != 0;
+                self.sweep.shift = value & 7;
+                self.sweep.reload = true;
+            }
+            2 => self.timer_period = (self.timer_period & 0x700) | value as u16,
+            3 => {
+                self.timer_period = (self.timer_period & 0xFF) | (((value & 7) as u16) << 8);
+                self.length_counter.load(value);
+                self.envelope.restart();
+                self.sequence_step = 0;
+            }
+            _ => unreachable!("Pulse register match failed - impossible!"),
+        }
+    }
+
+    fn sweep_target(&self) -> u16 {
+        let change = self.timer_period >> self.sweep.shift;
+        if self.sweep.negate {
+            // Pulse 1 negates with ones' complement, so it goes one lower than pulse 2
+            match self.channel {
+                PulseChannel::One => self.timer_period.wrapping_sub(change + 1),
+                PulseChannel::Two | PulseChannel::Mmc5 => self.timer_period.wrapping_sub(change),
+            }
+        } else {
+            self.timer_period + change
+        }
+    }
+
+    // The sweep unit mutes the channel even when it's disabled
+    fn is_muted(&self) -> bool {
+        if self.channel == PulseChannel::Mmc5 {
+            return false;
+        }
+        self.timer_period < 8 || (!self.sweep.negate && self.sweep_target() > 0x7FF)
+    }
+
+    // Clocked every APU cycle (every other CPU cycle)
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.sequence_step = (self.sequence_step + 1) % 8;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    // Clocked every half frame
+    pub fn clock_sweep(&mut self) {
+        if self.sweep.divider == 0 && self.sweep.enabled && self.sweep.shift != 0 && !self.is_muted() {
+            self.timer_period = self.sweep_target();
+        }
+
+        if self.sweep.divider == 0 || self.sweep.reload {
+            self.sweep.divider = self.sweep.period;
+            self.sweep.reload = false;
+        } else {
+            self.sweep.divider -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if DUTY_TABLE[self.duty as usize][self.sequence_step] == 0
+            || !self.length_counter.is_active()
+            || self.is_muted()
+        {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Timer period $100, sweep enabled with negate and a shift of 2
+    fn negated_sweep(channel: PulseChannel) -> Pulse {
+        let mut pulse = Pulse::new(channel);
+        pulse.write(1, 0x8A);
+        pulse.write(2, 0x00);
+        pulse.write(3, 0x01);
+        pulse
+    }
+
+    #[test]
+    fn pulse_1_negates_one_lower() {
+        assert_eq!(negated_sweep(PulseChannel::One).sweep_target(), 0xBF);
+        assert_eq!(negated_sweep(PulseChannel::Two).sweep_target(), 0xC0);
+    }
+
+    #[test]
+    fn sweep_updates_the_period() {
+        let mut pulse = negated_sweep(PulseChannel::One);
+        pulse.clock_sweep();
+        assert_eq!(pulse.timer_period, 0xBF);
+    }
+
+    #[test]
+    fn sweep_overflow_mutes() {
+        let mut pulse = Pulse::new(PulseChannel::Two);
+        pulse.write(1, 0x01);
+        pulse.write(2, 0xFF);
+        pulse.write(3, 0x07);
+        assert!(pulse.is_muted());
+        assert!(!negated_sweep(PulseChannel::Two).is_muted());
+    }
+}
@@ -0,0 +1,72 @@
+use crate::apu::length_counter::LengthCounter;
+
+const SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+#[derive(Default)]
+pub struct Triangle {
+    sequence_step: usize,
+    timer: u16,
+    timer_period: u16,
+    // The linear counter is a second, finer grained length counter
+    linear_counter: u8,
+    linear_reload_value: u8,
+    linear_reload: bool,
+    control: bool,
+    pub length_counter: LengthCounter,
+}
+
+impl Triangle {
+    // Takes the register offset inside the channel, $4008-$400B
+    pub fn write(&mut self, reg: u16, value: u8) {
+        match reg {
+            0 => {
+                // The control flag doubles as the length counter halt flag
+                self.control = value & 0x80 != 0;
+                self.length_counter.halt = self.control;
+                self.linear_reload_value = value & 0x7F;
+            }
+            1 => (), // Unused
+            2 => self.timer_period = (self.timer_period & 0x700) | value as u16,
+            3 => {
+                self.timer_period = (self.timer_period & 0xFF) | (((value & 7) as u16) << 8);
+                self.length_counter.load(value);
+                self.linear_reload = true;
+            }
+            _ => unreachable!("Triangle register match failed - impossible!"),
+        }
+    }
+
+    // Unlike the other channels this one is clocked on every CPU cycle
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            // Both counters have to be running for the sequencer to move
+            if self.linear_counter > 0 && self.length_counter.is_active() {
+                self.sequence_step = (self.sequence_step + 1) % SEQUENCE.len();
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    // Clocked every quarter frame
+    pub fn clock_linear_counter(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+
+        if !self.control {
+            self.linear_reload = false;
+        }
+    }
+
+    // Stopping the sequencer leaves the output where it was instead of silencing it
+    pub fn output(&self) -> u8 {
+        SEQUENCE[self.sequence_step]
+    }
+}
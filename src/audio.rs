@@ -0,0 +1,10 @@
+pub mod blip;
+#[cfg(feature = "audio-device")]
+pub mod device;
+pub mod filters;
+pub mod resampler;
+pub mod sink;
+pub mod stems;
+pub mod wav;
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
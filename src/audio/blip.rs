@@ -0,0 +1,106 @@
+use std::f64::consts::PI;
+
+/*
+Band limited step synthesis, the same idea as blargg's blip_buffer:
+instead of sampling the input level we record every change as a delta at its exact clock,
+spread over a few output samples with a windowed sinc, then integrate the deltas back into
+levels when reading. Each step comes out already low passed below the output Nyquist
+frequency, so there's no aliasing no matter how fast the input clock is.
+*/
+
+// Fractional positions a step can land on between 2 output samples
+const PHASES: usize = 64;
+const KERNEL_WIDTH: usize = 16;
+// Keep a little room below Nyquist for the window's transition band
+const CUTOFF: f64 = 0.45;
+
+pub struct BlipBuffer {
+    // Output samples per input clock
+    ratio: f64,
+    kernels: Vec<[f32; KERNEL_WIDTH]>,
+    // Unread output samples, as deltas
+    deltas: Vec<f32>,
+    // Output sample position of the current frame's clock 0
+    frame_start: f64,
+    available: usize,
+    integrator: f32,
+}
+
+impl BlipBuffer {
+    pub fn new(clock_rate: f64, sample_rate: f64) -> Self {
+        BlipBuffer {
+            ratio: sample_rate / clock_rate,
+            kernels: (0..PHASES).map(make_kernel).collect(),
+            deltas: vec![0.0; KERNEL_WIDTH],
+            frame_start: 0.0,
+            available: 0,
+            integrator: 0.0,
+        }
+    }
+
+    // Adds a level change at a clock relative to the start of the current frame
+    pub fn add_delta(&mut self, clock: u32, delta: f32) {
+        let position = self.frame_start + clock as f64 * self.ratio;
+        let sample = position.floor();
+        let phase = ((position - sample) * PHASES as f64) as usize;
+        let start = sample as usize;
+
+        if self.deltas.len() < start + KERNEL_WIDTH {
+            self.deltas.resize(start + KERNEL_WIDTH, 0.0);
+        }
+        for (out, weight) in self.deltas[start..].iter_mut().zip(&self.kernels[phase]) {
+            *out += delta * weight;
+        }
+    }
+
+    // Makes every sample before the given clock readable, the next frame starts there
+    pub fn end_frame(&mut self, clocks: u32) {
+        self.frame_start += clocks as f64 * self.ratio;
+        self.available = self.frame_start.floor() as usize;
+    }
+
+    pub fn read_samples(&mut self, out: &mut Vec<f32>) {
+        if self.deltas.len() < self.available {
+            self.deltas.resize(self.available, 0.0);
+        }
+
+        for delta in self.deltas.drain(..self.available) {
+            self.integrator += delta;
+            out.push(self.integrator);
+        }
+
+        self.frame_start -= self.available as f64;
+        self.available = 0;
+    }
+}
+
+// Windowed sinc impulse for a step landing phase/PHASES of a sample after the kernel's start,
+// normalized so a step always adds up to exactly its delta
+fn make_kernel(phase: usize) -> [f32; KERNEL_WIDTH] {
+    let offset = phase as f64 / PHASES as f64;
+    let half = KERNEL_WIDTH as f64 / 2.0;
+    let mut kernel = [0.0; KERNEL_WIDTH];
+
+    for (i, weight) in kernel.iter_mut().enumerate() {
+        let x = i as f64 - half - offset + 1.0;
+        let sinc = if x == 0.0 {
+            1.0
+        } else {
+            (PI * CUTOFF * 2.0 * x).sin() / (PI * CUTOFF * 2.0 * x)
+        };
+        // Blackman window over the kernel's width
+        let t = (x + half) / KERNEL_WIDTH as f64;
+        let window = if (0.0..=1.0).contains(&t) {
+            0.42 - 0.5 * (2.0 * PI * t).cos() + 0.08 * (4.0 * PI * t).cos()
+        } else {
+            0.0
+        };
+        *weight = (sinc * window) as f32;
+    }
+
+    let sum: f32 = kernel.iter().sum();
+    for weight in kernel.iter_mut() {
+        *weight /= sum;
+    }
+    kernel
+}
@@ -0,0 +1,83 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+use crate::audio::sink::AudioSink;
+
+// Anything past this many seconds of queued audio gets dropped, so we never lag behind
+const MAX_LATENCY: f32 = 0.1;
+
+// Plays samples on the default output device, the stream pulls them from a shared queue
+pub struct DeviceSink {
+    // Dropping the stream stops playback, so we have to hold on to it
+    _stream: cpal::Stream,
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    sample_rate: u32,
+}
+
+impl DeviceSink {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or("No audio output device")?;
+        let supported = device.default_output_config()?;
+        let sample_format = supported.sample_format();
+        let config: cpal::StreamConfig = supported.into();
+
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+        let stream = match sample_format {
+            cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, queue.clone())?,
+            cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, queue.clone())?,
+            cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, queue.clone())?,
+        };
+        stream.play()?;
+
+        Ok(DeviceSink {
+            _stream: stream,
+            queue,
+            sample_rate: config.sample_rate.0,
+        })
+    }
+}
+
+fn build_stream<T: cpal::Sample>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    queue: Arc<Mutex<VecDeque<f32>>>,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let channels = config.channels as usize;
+
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let mut queue = queue.lock().unwrap();
+            // Our audio is mono, every channel of a frame gets the same sample
+            for frame in data.chunks_mut(channels) {
+                let sample = queue.pop_front().unwrap_or(0.0);
+                for out in frame.iter_mut() {
+                    *out = cpal::Sample::from::<f32>(&sample);
+                }
+            }
+        },
+        |err| eprintln!("Audio stream error: {}", err),
+    )
+}
+
+impl AudioSink for DeviceSink {
+    fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn write_samples(&mut self, samples: &[f32]) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.extend(samples);
+
+        let max_len = (self.sample_rate as f32 * MAX_LATENCY) as usize;
+        if queue.len() > max_len {
+            let excess = queue.len() - max_len;
+            queue.drain(..excess);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use crate::audio::blip::BlipBuffer;
+use crate::audio::filters::NesFilterChain;
+
+// Turns a level sampled on every input clock into filtered samples at the output rate
+pub struct Resampler {
+    blip: BlipBuffer,
+    filters: NesFilterChain,
+    sample_rate: u32,
+    last_level: f32,
+    // Clocks since the last read
+    clock: u32,
+}
+
+impl Resampler {
+    pub fn new(clock_rate: f64, sample_rate: u32) -> Self {
+        Resampler {
+            blip: BlipBuffer::new(clock_rate, sample_rate as f64),
+            filters: NesFilterChain::new(sample_rate as f32),
+            sample_rate,
+            last_level: 0.0,
+            clock: 0,
+        }
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // Feeds the level for one input clock, only changes cost anything
+    pub fn push(&mut self, level: f32) {
+        if level != self.last_level {
+            self.blip.add_delta(self.clock, level - self.last_level);
+            self.last_level = level;
+        }
+        self.clock += 1;
+    }
+
+    pub fn read_samples(&mut self, out: &mut Vec<f32>) {
+        self.blip.end_frame(self.clock);
+        self.clock = 0;
+
+        let start = out.len();
+        self.blip.read_samples(out);
+        for sample in out[start..].iter_mut() {
+            *sample = self.filters.process(*sample);
+        }
+    }
+}
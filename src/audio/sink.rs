@@ -0,0 +1,61 @@
+// Anything that can take the emulator's mono samples, all of them between -1 and 1
+pub trait AudioSink {
+    fn get_sample_rate(&self) -> u32;
+    fn write_samples(&mut self, samples: &[f32]);
+
+    // Called once before the emulator exits, for sinks that have to finalize something
+    fn finish(&mut self) {}
+}
+
+// Throws everything away, for when there's no sound card or nobody listening
+pub struct NullSink {
+    sample_rate: u32,
+}
+
+impl NullSink {
+    pub fn new(sample_rate: u32) -> Self {
+        NullSink { sample_rate }
+    }
+}
+
+impl AudioSink for NullSink {
+    fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn write_samples(&mut self, _samples: &[f32]) {}
+}
+
+// Sends everything to two sinks, like the sound card and a WAV file. Both have to run at
+// the first one's sample rate
+pub struct TeeSink {
+    first: Box<dyn AudioSink>,
+    second: Box<dyn AudioSink>,
+}
+
+impl TeeSink {
+    pub fn new(first: Box<dyn AudioSink>, second: Box<dyn AudioSink>) -> Self {
+        assert_eq!(
+            first.get_sample_rate(),
+            second.get_sample_rate(),
+            "Both sides of a TeeSink need the same sample rate"
+        );
+        TeeSink { first, second }
+    }
+}
+
+impl AudioSink for TeeSink {
+    fn get_sample_rate(&self) -> u32 {
+        self.first.get_sample_rate()
+    }
+
+    fn write_samples(&mut self, samples: &[f32]) {
+        self.first.write_samples(samples);
+        self.second.write_samples(samples);
+    }
+
+    fn finish(&mut self) {
+        self.first.finish();
+        self.second.finish();
+    }
+}
@@ -0,0 +1,40 @@
+use std::io;
+
+use crate::apu::mixer::CHANNELS;
+use crate::audio::sink::AudioSink;
+use crate::audio::wav::WavSink;
+
+// Writes <prefix>.mixed.wav and a <prefix>.<channel>.wav for every channel, sound chips
+// the cartridge doesn't have included
+pub struct StemWriter {
+    mixed: WavSink,
+    channels: Vec<WavSink>,
+}
+
+impl StemWriter {
+    pub fn create(prefix: &str, sample_rate: u32) -> io::Result<Self> {
+        let mixed = WavSink::create(&format!("{}.mixed.wav", prefix), sample_rate)?;
+        let channels = CHANNELS
+            .iter()
+            .map(|channel| {
+                WavSink::create(&format!("{}.{}.wav", prefix, channel.get_name()), sample_rate)
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(StemWriter { mixed, channels })
+    }
+
+    pub fn write_samples(&mut self, mixed: &[f32], channels: &[Vec<f32>]) {
+        self.mixed.write_samples(mixed);
+        for (sink, samples) in self.channels.iter_mut().zip(channels) {
+            sink.write_samples(samples);
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.mixed.finish();
+        for sink in self.channels.iter_mut() {
+            sink.finish();
+        }
+    }
+}
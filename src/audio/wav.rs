@@ -0,0 +1,121 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+
+use crate::audio::sink::AudioSink;
+
+const HEADER_SIZE: u32 = 44;
+const BYTES_PER_SAMPLE: u32 = 2;
+
+// Writes 16 bit mono PCM, the sizes in the header get fixed up when finishing
+pub struct WavSink {
+    file: BufWriter<File>,
+    sample_rate: u32,
+    samples_written: u32,
+    finished: bool,
+}
+
+impl WavSink {
+    pub fn create(filename: &str, sample_rate: u32) -> io::Result<Self> {
+        let mut sink = WavSink {
+            file: BufWriter::new(File::create(filename)?),
+            sample_rate,
+            samples_written: 0,
+            finished: false,
+        };
+        sink.write_header()?;
+        Ok(sink)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let data_size = self.samples_written * BYTES_PER_SAMPLE;
+
+        self.file.write_all(b"RIFF")?;
+        self.file.write_all(&(HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+        self.file.write_all(b"WAVE")?;
+
+        self.file.write_all(b"fmt ")?;
+        self.file.write_all(&16u32.to_le_bytes())?; // fmt chunk size
+        self.file.write_all(&1u16.to_le_bytes())?; // PCM
+        self.file.write_all(&1u16.to_le_bytes())?; // Mono
+        self.file.write_all(&self.sample_rate.to_le_bytes())?;
+        self.file.write_all(&(self.sample_rate * BYTES_PER_SAMPLE).to_le_bytes())?; // Byte rate
+        self.file.write_all(&(BYTES_PER_SAMPLE as u16).to_le_bytes())?; // Block align
+        self.file.write_all(&16u16.to_le_bytes())?; // Bits per sample
+
+        self.file.write_all(b"data")?;
+        self.file.write_all(&data_size.to_le_bytes())
+    }
+
+    fn try_write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        for sample in samples {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.samples_written += samples.len() as u32;
+        Ok(())
+    }
+
+    fn try_finish(&mut self) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.flush()
+    }
+}
+
+impl AudioSink for WavSink {
+    fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn write_samples(&mut self, samples: &[f32]) {
+        self.try_write_samples(samples)
+            .expect("Couldn't write to WAV file");
+    }
+
+    fn finish(&mut self) {
+        if !self.finished {
+            self.try_finish().expect("Couldn't finish WAV file");
+            self.finished = true;
+        }
+    }
+}
+
+impl Drop for WavSink {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+    use std::fs;
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn finish_fixes_up_the_sizes() {
+        let path = std::env::temp_dir().join(format!("nust_wav_test_{}.wav", std::process::id()));
+        let filename = path.to_str().unwrap();
+
+        let mut sink = WavSink::create(filename, 44100).unwrap();
+        sink.write_samples(&[0.0, 0.5, -0.5, 1.0, -1.0]);
+        sink.finish();
+        drop(sink);
+
+        let bytes = fs::read(filename).unwrap();
+        fs::remove_file(filename).unwrap();
+        assert_eq!(bytes.len(), HEADER_SIZE as usize + 10);
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(read_u32(&bytes, 4), HEADER_SIZE - 8 + 10);
+        assert_eq!(read_u32(&bytes, 24), 44100);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(read_u32(&bytes, 40), 10);
+        let header = HEADER_SIZE as usize;
+        assert_eq!(bytes[header + 6..header + 8], i16::MAX.to_le_bytes());
+    }
+}
@@ -1,55 +1,80 @@
+use crate::apu::mixer::ChannelControls;
+use crate::apu::Apu;
 use crate::nes_parser::{Cartridge};
+use crate::ppu::Ppu;
+use controller::{Buttons, Controller};
 
+pub mod controller;
 pub mod mappers;
 
 pub struct Bus {
     ram: [u8; 0x800],
     crt: Cartridge,
+    ppu: Ppu,
+    apu: Apu,
+    controllers: [Controller; 2],
+    // Last value on the CPU data bus, what unmapped and partially mapped reads see
+    open_bus: u8,
     cycles: usize,
+    // Page written to $4014, the DMA runs once the writing instruction is done
+    oam_dma_page: Option<u8>,
+    oam_dma_active: bool,
+    // The CPU makes an instruction's accesses before the bus ticks through its cycles, so
+    // this is where the current one started, how long it is and how many of its last
+    // cycles are writes
+    instruction_start: usize,
+    instruction_cycles: u8,
+    instruction_writes: u8,
 }
 
 impl Bus {
-    pub fn cpu_read(&self, addr: u16) -> u8 {
-        match addr {
+    pub fn cpu_read(&mut self, addr: u16) -> u8 {
+        let value = match addr {
             0x0000..=0x1FFF => self.ram[(addr & 0x7FF) as usize],
-            0x2000..=0x401F => {
-                println!("Warning: Unimplemented PPU register read mapping - current instruction did nothing");
-                0
-                // Warn so I can test TODO implement PPU register mappings
-            }
-            0x4020..=0x5FFF => todo!("Expansion ROM"),
-            0x6000..=0x7FFF => todo!("SRAM and saving mechanisms"),
-            0x8000..=0xFFFF => {
-                self.crt.prg_rom[self.crt.mapper.cpu_map_read(addr).unwrap_or_default() as usize]
-            }
-            _ => {
-                unreachable!("Bus reading match failed - impossible!")
-            }
-        }
+            0x2000..=0x3FFF => self.ppu.cpu_read(addr, &mut self.crt),
+            // $4015 isn't driven by the APU on bit 5
+            0x4015 => self.apu.read_status() | (self.open_bus & 0x20),
+            // Only the low bits are driven by the controller port
+            0x4016 => self.controllers[0].read() | (self.open_bus & 0xE0),
+            0x4017 => self.controllers[1].read() | (self.open_bus & 0xE0),
+            0x4000..=0x401F => self.open_bus,
+            0x4020..=0xFFFF => self.crt.cpu_read(addr).unwrap_or(self.open_bus),
+        };
+
+        self.open_bus = value;
+        value
     }
 
-    pub fn cpu_read_word(&self, addr: u16) -> u16 {
+    pub fn cpu_read_word(&mut self, addr: u16) -> u16 {
         (self.cpu_read(addr) as u16) | ((self.cpu_read(addr + 1) as u16) << 8)
     }
 
-    pub fn cpu_read_zp_word(&self, addr: u8) -> u16 {
+    pub fn cpu_read_zp_word(&mut self, addr: u8) -> u16 {
         (self.cpu_read(addr as u16) as u16)
             | ((self.cpu_read(addr.wrapping_add(1) as u16) as u16) << 8)
     }
 
     pub fn cpu_write(&mut self, addr: u16, value: u8) {
+        self.open_bus = value;
+
         match addr {
-            0x0000..=0x1FFF => {
-                println!("RAMWRT: {:02X} -> {:04X}", value, addr & 0x7FF);
-                self.ram[(addr & 0x07FF) as usize] = value;
-            }
+            0x0000..=0x1FFF => self.ram[(addr & 0x07FF) as usize] = value,
             0x2000..=0x3FFF => {
-                println!("Warning: Unimplemented PPU register write mapping - current instruction did nothing")
-                // Warn so I can test TODO implement PPU register mappings
+                self.ppu.cpu_write(addr, value, &mut self.crt);
+                self.crt.mapper.ppu_register_write(addr, value);
+            }
+            0x4014 => self.oam_dma_page = Some(value),
+            0x4016 => {
+                // Both ports share the strobe line
+                for controller in self.controllers.iter_mut() {
+                    controller.write_strobe(value);
+                }
             }
-            _ => {
-                self.crt.mapper.cpu_map_write(addr, value);
+            0x4000..=0x4013 | 0x4015 | 0x4017 => {
+                self.apu.cpu_write(addr, value, self.write_cycle())
             }
+            0x4000..=0x401F => (),
+            0x4020..=0xFFFF => self.crt.cpu_write(addr, value),
         }
     }
 
@@ -64,18 +89,152 @@ impl Bus {
     }
 
     pub fn cycle(&mut self, cycles: u8) {
-        self.cycles += cycles as usize
+        self.tick(cycles as usize);
+
+        if let Some(page) = self.oam_dma_page.take() {
+            self.oam_dma(page);
+        }
+    }
+
+    fn tick(&mut self, cycles: usize) {
+        let mut remaining = cycles;
+        while remaining > 0 {
+            // The PPU runs 3 dots for every CPU cycle, the APU and mapper run on the CPU clock
+            for _ in 0..3 {
+                self.ppu.clock(&mut self.crt);
+            }
+            self.crt.mapper.cpu_clock();
+            self.apu.clock(self.cycles, &self.crt.mapper.audio_output());
+            self.cycles += 1;
+            remaining -= 1;
+
+            // DMC sample fetches halt the CPU for 4 cycles, 3 if the halt lands on a write the
+            // CPU has to finish first and 2 if OAM DMA already halted it. The real stall also
+            // depends on the APU's get/put alignment and can go down to 1, that isn't modelled
+            if let Some(addr) = self.apu.dmc_dma_request() {
+                let value = self.cpu_read(addr);
+                self.apu.dmc_dma_fill(value);
+                remaining += if self.oam_dma_active {
+                    2
+                } else if self.is_write_cycle(self.cycles) {
+                    3
+                } else {
+                    4
+                };
+            }
+        }
+    }
+
+    pub fn begin_instruction(&mut self, cycles: u8, write_cycles: u8) {
+        self.instruction_start = self.cycles;
+        self.instruction_cycles = cycles;
+        self.instruction_writes = write_cycles;
+    }
+
+    fn is_write_cycle(&self, cycle: usize) -> bool {
+        let end = self.instruction_start + self.instruction_cycles as usize;
+        (end - self.instruction_writes as usize..end).contains(&cycle)
+    }
+
+    // Writes happen on the last cycle of the instructions that make them, outside of an
+    // instruction that's the cycle the bus is on
+    fn write_cycle(&self) -> usize {
+        let end = self.instruction_start + self.instruction_cycles as usize;
+        end.saturating_sub(1).max(self.cycles)
+    }
+
+    // Copies a page to OAM through $2004, the CPU is halted the whole time
+    fn oam_dma(&mut self, page: u8) {
+        // 1 halt cycle, 1 more if we have to wait for a read cycle, then 256 read/write pairs
+        let stall = if self.cycles % 2 == 1 { 514 } else { 513 };
+
+        let base = (page as u16) << 8;
+        for offset in 0..0x100 {
+            let value = self.cpu_read(base + offset);
+            self.ppu.cpu_write(0x2004, value, &mut self.crt);
+        }
+
+        self.oam_dma_active = true;
+        self.tick(stall);
+        self.oam_dma_active = false;
+    }
+
+    // The reset button doesn't reach RAM or the cartridge, only the PPU and APU
+    pub fn reset(&mut self) {
+        self.ppu.reset();
+        self.apu.reset();
+        // Mappers snooping PPUCTRL and PPUMASK see them cleared as well
+        self.crt.mapper.ppu_register_write(0x2000, 0);
+        self.crt.mapper.ppu_register_write(0x2001, 0);
     }
 
     pub fn get_cycles(&self) -> usize {
         self.cycles
     }
 
+    // Interrupt lines, active high here even though the real ones are active low
+    pub fn nmi_line(&self) -> bool {
+        self.ppu.nmi_line()
+    }
+
+    pub fn irq_line(&self) -> bool {
+        self.apu.irq_line() || self.crt.mapper.irq_line()
+    }
+
+    pub fn set_buttons(&mut self, player: usize, buttons: Buttons) {
+        self.controllers[player].set_buttons(buttons);
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.apu.set_sample_rate(sample_rate);
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.apu.get_sample_rate()
+    }
+
+    pub fn get_channel_controls(&self) -> &ChannelControls {
+        self.apu.get_channel_controls()
+    }
+
+    pub fn get_channel_controls_mut(&mut self) -> &mut ChannelControls {
+        self.apu.get_channel_controls_mut()
+    }
+
+    pub fn take_audio_samples(&mut self) -> Vec<f32> {
+        self.apu.take_samples()
+    }
+
+    pub fn set_stems_enabled(&mut self, enabled: bool) {
+        self.apu.set_stems_enabled(enabled);
+    }
+
+    pub fn get_stems_enabled(&self) -> bool {
+        self.apu.get_stems_enabled()
+    }
+
+    pub fn take_stem_samples(&mut self) -> Vec<Vec<f32>> {
+        self.apu.take_stem_samples()
+    }
+
+    pub fn get_ppu(&self) -> &Ppu {
+        &self.ppu
+    }
+
     pub fn create_from_crt(crt: Cartridge) -> Self {
         Bus {
             ram: [0; 0x800],
             crt,
-            cycles: 7,
+            ppu: Ppu::new(),
+            apu: Apu::new(),
+            controllers: Default::default(),
+            open_bus: 0,
+            cycles: 0,
+            oam_dma_page: None,
+            oam_dma_active: false,
+            instruction_start: 0,
+            instruction_cycles: 0,
+            instruction_writes: 0,
         }
     }
 }
@@ -0,0 +1,51 @@
+use bitflags::bitflags;
+
+bitflags! {
+    // In the order the shift register sends them out
+    #[derive(Default)]
+    pub struct Buttons: u8 {
+        const A =       0b00000001;
+        const B =       0b00000010;
+        const SELECT =  0b00000100;
+        const START =   0b00001000;
+        const UP =      0b00010000;
+        const DOWN =    0b00100000;
+        const LEFT =    0b01000000;
+        const RIGHT =   0b10000000;
+    }
+}
+
+// Standard joypad, a 4021 shift register latching the buttons while strobe is high
+#[derive(Default)]
+pub struct Controller {
+    buttons: Buttons,
+    shift: u8,
+    strobe: bool,
+}
+
+impl Controller {
+    pub fn set_buttons(&mut self, buttons: Buttons) {
+        self.buttons = buttons;
+        if self.strobe {
+            self.shift = buttons.bits();
+        }
+    }
+
+    pub fn write_strobe(&mut self, value: u8) {
+        self.strobe = value & 1 != 0;
+        if self.strobe {
+            self.shift = self.buttons.bits();
+        }
+    }
+
+    // Returns the next button on bit 0, official pads return 1 after all 8 were read
+    pub fn read(&mut self) -> u8 {
+        if self.strobe {
+            return self.buttons.bits() & 1;
+        }
+
+        let bit = self.shift & 1;
+        self.shift = (self.shift >> 1) | 0x80;
+        bit
+    }
+}
@@ -1,13 +1,75 @@
-use crate::nes_parser::InesFile;
+use crate::apu::expansion::fds::FdsAudio;
+use crate::apu::expansion::ExpansionOutput;
+use crate::apu::expansion::mmc5::Mmc5Audio;
+use crate::apu::expansion::namco_163::Namco163Audio;
+use crate::apu::expansion::sunsoft_5b::Sunsoft5bAudio;
+use crate::apu::expansion::vrc6::Vrc6Audio;
+use crate::apu::expansion::vrc7::Vrc7Audio;
+use crate::nes_parser::{InesFile, InesFlags6, Mirroring};
+use crate::nsf_parser::NsfExpansion;
 
 mod mapper_0;
+mod mapper_1;
+mod mapper_11;
+mod mapper_19;
+mod mapper_2;
+mod mapper_24;
 mod mapper_3;
+mod mapper_34;
+mod mapper_4;
+mod mapper_5;
+mod mapper_66;
+mod mapper_69;
+mod mapper_7;
+mod mapper_85;
+mod nsf;
+mod vrc_irq;
 
 pub trait Mapper {
-    fn cpu_map_read(&self, addr: u16) -> Option<u16>;
-    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<u16>;
-    fn ppu_map_read(&self, addr: u16) -> Option<u16>;
-    fn ppu_map_write(&mut self, addr: u16, value: u8) -> Option<u16>;
+    fn cpu_map_read(&self, addr: u16) -> Option<usize>;
+    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<usize>;
+    // Sees every pattern table fetch the PPU makes, for mappers that watch its address lines
+    fn ppu_map_read(&mut self, addr: u16) -> Option<usize>;
+    fn ppu_map_write(&mut self, addr: u16, value: u8) -> Option<usize>;
+
+    // CPU writes to $2000-$3FFF, for mappers that keep an eye on PPUCTRL and PPUMASK
+    fn ppu_register_write(&mut self, _addr: u16, _value: u8) {}
+
+    // Offsets into the cartridge's PRG RAM, most boards either have 8Kib at $6000 or
+    // nothing there at all, so everyone gets the RAM unless they say otherwise
+    fn prg_ram_map_read(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x6000..=0x7FFF => Some((addr & 0x1FFF) as usize),
+            _ => None,
+        }
+    }
+
+    fn prg_ram_map_write(&mut self, addr: u16) -> Option<usize> {
+        self.prg_ram_map_read(addr)
+    }
+
+    // Mirroring set by the mapper at runtime, None if it's hardwired on the board
+    fn mirroring(&self) -> Option<Mirroring> {
+        None
+    }
+
+    // Whether the cartridge is holding the CPU's IRQ line
+    fn irq_line(&self) -> bool {
+        false
+    }
+
+    // Registers the mapper answers reads with itself instead of mapping them to memory
+    fn cpu_read_register(&mut self, _addr: u16) -> Option<u8> {
+        None
+    }
+
+    // Called on every CPU cycle, for IRQ timers and sound chips
+    fn cpu_clock(&mut self) {}
+
+    // Levels of the cartridge's sound chips, in the same units as the APU's mixer output
+    fn audio_output(&self) -> ExpansionOutput {
+        ExpansionOutput::default()
+    }
 }
 
 pub fn get_mapper(ines: &InesFile) -> Option<Box<dyn Mapper>> {
@@ -16,11 +78,123 @@ pub fn get_mapper(ines: &InesFile) -> Option<Box<dyn Mapper>> {
             prg_banks: ines.header.prg_size,
             chr_banks: ines.header.chr_size,
         })),
+        1 => Some(Box::new(mapper_1::Mapper1::new(
+            ines.header.prg_size,
+            ines.header.chr_size,
+            ines.header.prg_ram_size.max(1) * 0x2000,
+        ))),
+        2 => Some(Box::new(mapper_2::Mapper2::new(
+            ines.header.prg_size,
+            ines.header.chr_size,
+        ))),
         3 => Some(Box::new(mapper_3::Mapper3 {
             prg_banks: ines.header.prg_size,
             chr_banks: ines.header.chr_size,
             current_chrbank: 0,
         })),
+        // Submapper 1 is the MMC6. iNES 1.0 headers have no way to say so, so StarTropics
+        // and its sequel only get the MMC6's RAM from a NES 2.0 dump
+        4 => Some(Box::new(mapper_4::Mapper4::new(
+            ines.header.prg_size,
+            ines.header.chr_size,
+            ines.header.submapper == 1,
+            ines.header.flags.flags6.contains(InesFlags6::FOUR_SCREEN),
+        ))),
+        5 => Some(Box::new(mapper_5::Mapper5::new(
+            ines.header.prg_size,
+            ines.header.chr_size,
+            ines.header.prg_ram_size.max(1) * 0x2000,
+        ))),
+        7 => Some(Box::new(mapper_7::Mapper7::new(
+            ines.header.prg_size,
+            ines.header.chr_size,
+        ))),
+        11 => Some(Box::new(mapper_11::Mapper11::new(
+            ines.header.prg_size,
+            ines.header.chr_size,
+        ))),
+        19 => Some(Box::new(mapper_19::Mapper19::new(
+            ines.header.prg_size,
+            ines.header.chr_size,
+        ))),
+        24 => Some(Box::new(mapper_24::Mapper24::new(
+            ines.header.prg_size,
+            ines.header.chr_size,
+            false,
+        ))),
+        26 => Some(Box::new(mapper_24::Mapper24::new(
+            ines.header.prg_size,
+            ines.header.chr_size,
+            true,
+        ))),
+        34 => Some(Box::new(mapper_34::Mapper34::new(
+            ines.header.prg_size,
+            ines.header.chr_size,
+            ines.header.submapper,
+        ))),
+        66 => Some(Box::new(mapper_66::Mapper66::new(
+            ines.header.prg_size,
+            ines.header.chr_size,
+        ))),
+        69 => Some(Box::new(mapper_69::Mapper69::new(
+            ines.header.prg_size,
+            ines.header.chr_size,
+        ))),
+        85 => Some(Box::new(mapper_85::Mapper85::new(
+            ines.header.prg_size,
+            ines.header.chr_size,
+        ))),
         _ => None,
     }
 }
+
+// FDS tunes also get their $6000-$DFFF RAM loaded, fds_banks are the banks for $6000-$7FFF
+pub fn get_nsf_mapper(
+    banks: [u8; 8],
+    bankswitched: bool,
+    prg_rom: &[u8],
+    fds_banks: [u8; 2],
+    expansion: NsfExpansion,
+) -> Box<dyn Mapper> {
+    let mmc5 = expansion.contains(NsfExpansion::MMC5);
+    let fds = expansion.contains(NsfExpansion::FDS);
+    let mut mapper = nsf::NsfMapper {
+        banks,
+        bankswitched,
+        bank_count: prg_rom.len() / 0x1000,
+        vrc6: if expansion.contains(NsfExpansion::VRC6) {
+            Some(Vrc6Audio::default())
+        } else {
+            None
+        },
+        vrc7: if expansion.contains(NsfExpansion::VRC7) {
+            Some(Vrc7Audio::new())
+        } else {
+            None
+        },
+        sunsoft_5b: if expansion.contains(NsfExpansion::S5B) {
+            Some(Sunsoft5bAudio::new())
+        } else {
+            None
+        },
+        namco_163: if expansion.contains(NsfExpansion::N163) {
+            Some(Namco163Audio::new())
+        } else {
+            None
+        },
+        fds: if fds { Some(FdsAudio::new()) } else { None },
+        fds_ram: if fds { vec![0; 0x8000] } else { vec![] },
+        prg: if fds { prg_rom.to_vec() } else { vec![] },
+        mmc5: if mmc5 { Some(Mmc5Audio::new()) } else { None },
+        mmc5_exram: if mmc5 { vec![0; 0x400] } else { vec![] },
+        mmc5_multiplicands: [0; 2],
+    };
+
+    if fds {
+        let windows = fds_banks.iter().chain(banks[..6].iter());
+        for (window, &bank) in windows.enumerate() {
+            mapper.fds_load_bank(window, bank);
+        }
+    }
+    Box::new(mapper)
+}
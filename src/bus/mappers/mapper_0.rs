@@ -6,27 +6,36 @@ pub(crate) struct Mapper0 {
 }
 
 impl Mapper for Mapper0 {
-    fn cpu_map_read(&self, addr: u16) -> Option<u16> {
+    fn cpu_map_read(&self, addr: u16) -> Option<usize> {
         if addr & 0x8000 != 0 {
-            Some(addr & (if self.prg_banks > 1 { 0x7FFF } else { 0x3FFF }))
+            Some((addr & (if self.prg_banks > 1 { 0x7FFF } else { 0x3FFF })) as usize)
         } else {
             None
         }
     }
 
-    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<u16> {
+    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<usize> {
         if addr & 0x8000 != 0 {
-            Some(addr & (if self.prg_banks > 1 { 0x7FFF } else { 0x3FFF }))
+            Some((addr & (if self.prg_banks > 1 { 0x7FFF } else { 0x3FFF })) as usize)
         } else {
             None
         }
     }
 
-    fn ppu_map_read(&self, addr: u16) -> Option<u16> {
-        todo!("PPU read")
+    fn ppu_map_read(&mut self, addr: u16) -> Option<usize> {
+        if addr < 0x2000 {
+            Some(addr as usize)
+        } else {
+            None
+        }
     }
 
-    fn ppu_map_write(&mut self, addr: u16, value: u8) -> Option<u16> {
-        todo!("PPU write")
+    fn ppu_map_write(&mut self, addr: u16, _value: u8) -> Option<usize> {
+        // Only writable if it's CHR RAM
+        if addr < 0x2000 && self.chr_banks == 0 {
+            Some(addr as usize)
+        } else {
+            None
+        }
     }
 }
@@ -0,0 +1,199 @@
+use crate::bus::mappers::Mapper;
+use crate::nes_parser::Mirroring;
+
+// Nintendo MMC1, found on the SxROM boards. Registers are loaded serially through
+// bit 0 of 5 writes to $8000-$FFFF, the address of the last one picks the register
+pub(crate) struct Mapper1 {
+    // In 16Kib units
+    prg_banks: usize,
+    // In 4Kib units, CHR RAM if the cartridge had no CHR ROM
+    chr_banks: usize,
+    chr_ram: bool,
+    // In 8Kib units, SOROM has 2 and SXROM 4
+    prg_ram_banks: usize,
+    shift: u8,
+    shift_count: u8,
+    // Bits 0-1 mirroring, 2-3 PRG mode, 4 CHR mode
+    control: u8,
+    chr_bank: [u8; 2],
+    // Bit 4 disables PRG RAM
+    prg_bank: u8,
+    // Writes on the cycle right after another one are ignored, which is what happens
+    // to the second write of a read-modify-write instruction
+    written_this_cycle: bool,
+}
+
+impl Mapper1 {
+    pub fn new(prg_size: u8, chr_size: u8, prg_ram_size: usize) -> Self {
+        Mapper1 {
+            prg_banks: prg_size.max(1) as usize,
+            chr_banks: chr_size.max(1) as usize * 2,
+            chr_ram: chr_size == 0,
+            prg_ram_banks: (prg_ram_size / 0x2000).max(1),
+            shift: 0,
+            shift_count: 0,
+            control: 0x0C,
+            chr_bank: [0; 2],
+            prg_bank: 0,
+            written_this_cycle: false,
+        }
+    }
+
+    // Boards with more than 256Kib of PRG (SUROM, SXROM) use bit 4 of the CHR bank to
+    // pick which half the PRG banks come from. Real hardware takes it from whichever
+    // CHR register the PPU is using, games keep both the same
+    fn prg_outer_bank(&self) -> usize {
+        if self.prg_banks > 16 {
+            (self.chr_bank[0] & 0x10) as usize
+        } else {
+            0
+        }
+    }
+
+    fn prg_offset(&self, bank: usize, addr: u16) -> usize {
+        ((self.prg_outer_bank() | bank) % self.prg_banks) * 0x4000 + (addr & 0x3FFF) as usize
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x8000..=0x9FFF => self.control = value,
+            0xA000..=0xBFFF => self.chr_bank[0] = value,
+            0xC000..=0xDFFF => self.chr_bank[1] = value,
+            _ => self.prg_bank = value,
+        }
+    }
+}
+
+impl Mapper for Mapper1 {
+    fn cpu_map_read(&self, addr: u16) -> Option<usize> {
+        let bank = (self.prg_bank & 0x0F) as usize;
+        match (addr, (self.control >> 2) & 3) {
+            (0x0000..=0x7FFF, _) => None,
+            // 32Kib mode ignores the low bit of the bank
+            (_, 0 | 1) => Some(self.prg_offset(bank & !1 | ((addr >> 14) & 1) as usize, addr)),
+            // First bank fixed at $8000
+            (0x8000..=0xBFFF, 2) => Some(self.prg_offset(0, addr)),
+            (_, 2) => Some(self.prg_offset(bank, addr)),
+            // Last bank fixed at $C000
+            (0x8000..=0xBFFF, _) => Some(self.prg_offset(bank, addr)),
+            _ => Some(self.prg_offset(0x0F, addr)),
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<usize> {
+        if addr < 0x8000 || self.written_this_cycle {
+            return None;
+        }
+        self.written_this_cycle = true;
+
+        // Bit 7 resets the shift register and goes back to the fixed last bank mode
+        if value & 0x80 != 0 {
+            self.shift = 0;
+            self.shift_count = 0;
+            self.control |= 0x0C;
+            return None;
+        }
+
+        self.shift |= (value & 1) << self.shift_count;
+        self.shift_count += 1;
+        if self.shift_count == 5 {
+            self.write_register(addr, self.shift);
+            self.shift = 0;
+            self.shift_count = 0;
+        }
+        None
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> Option<usize> {
+        if addr >= 0x2000 {
+            return None;
+        }
+
+        let bank = if self.control & 0x10 != 0 {
+            self.chr_bank[(addr >> 12) as usize] as usize
+        } else {
+            // 8Kib mode ignores the low bit of the bank
+            (self.chr_bank[0] & !1) as usize | (addr >> 12) as usize
+        };
+        Some((bank % self.chr_banks) * 0x1000 + (addr & 0xFFF) as usize)
+    }
+
+    fn ppu_map_write(&mut self, addr: u16, _value: u8) -> Option<usize> {
+        if self.chr_ram {
+            self.ppu_map_read(addr)
+        } else {
+            None
+        }
+    }
+
+    // SOROM and SXROM bank their extra PRG RAM through the CHR bank register too
+    fn prg_ram_map_read(&self, addr: u16) -> Option<usize> {
+        if !(0x6000..=0x7FFF).contains(&addr) || self.prg_bank & 0x10 != 0 {
+            return None;
+        }
+
+        let bank = match self.prg_ram_banks {
+            4 => (self.chr_bank[0] >> 2) & 3,
+            2 => (self.chr_bank[0] >> 3) & 1,
+            _ => 0,
+        };
+        Some(bank as usize * 0x2000 + (addr & 0x1FFF) as usize)
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        Some(match self.control & 3 {
+            0 => Mirroring::SingleScreenA,
+            1 => Mirroring::SingleScreenB,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        })
+    }
+
+    fn cpu_clock(&mut self) {
+        self.written_this_cycle = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 5 serial writes, bit 0 first, with a CPU cycle between each
+    fn load(mapper: &mut Mapper1, addr: u16, value: u8) {
+        for bit in 0..5 {
+            mapper.cpu_map_write(addr, value >> bit);
+            mapper.cpu_clock();
+        }
+    }
+
+    #[test]
+    fn serial_load_sets_the_prg_bank() {
+        let mut mapper = Mapper1::new(8, 0, 0x2000);
+        load(&mut mapper, 0xE000, 3);
+        assert_eq!(mapper.cpu_map_read(0x8000), Some(3 * 0x4000));
+        assert_eq!(mapper.cpu_map_read(0xC000), Some(7 * 0x4000));
+    }
+
+    #[test]
+    fn ignores_consecutive_writes() {
+        let mut mapper = Mapper1::new(8, 0, 0x2000);
+        mapper.cpu_map_write(0xE000, 1);
+        // The dummy write of a read-modify-write, one cycle before the real one
+        mapper.cpu_map_write(0xE000, 0);
+        mapper.cpu_clock();
+        load(&mut mapper, 0xE000, 0);
+        assert_eq!(mapper.shift_count, 1);
+        assert_eq!(mapper.prg_bank, 1);
+    }
+
+    #[test]
+    fn bit_7_resets_the_shift_register() {
+        let mut mapper = Mapper1::new(8, 0, 0x2000);
+        mapper.cpu_map_write(0xE000, 1);
+        mapper.cpu_clock();
+        mapper.cpu_map_write(0x8000, 0x80);
+        mapper.cpu_clock();
+        load(&mut mapper, 0xE000, 2);
+        assert_eq!(mapper.prg_bank, 2);
+    }
+}
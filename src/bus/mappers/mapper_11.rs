@@ -0,0 +1,52 @@
+use crate::bus::mappers::Mapper;
+
+// Color Dreams, one register with a 32Kib PRG bank in the low bits and an 8Kib CHR bank
+// in the high ones
+pub(crate) struct Mapper11 {
+    // In 32Kib units
+    prg_banks: usize,
+    // In 8Kib units
+    chr_banks: usize,
+    register: u8,
+}
+
+impl Mapper11 {
+    pub fn new(prg_size: u8, chr_size: u8) -> Self {
+        Mapper11 {
+            prg_banks: (prg_size as usize / 2).max(1),
+            chr_banks: chr_size.max(1) as usize,
+            register: 0,
+        }
+    }
+}
+
+impl Mapper for Mapper11 {
+    fn cpu_map_read(&self, addr: u16) -> Option<usize> {
+        if addr & 0x8000 != 0 {
+            let bank = (self.register & 3) as usize % self.prg_banks;
+            Some(bank * 0x8000 + (addr & 0x7FFF) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<usize> {
+        if addr & 0x8000 != 0 {
+            self.register = value;
+        }
+        None
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> Option<usize> {
+        if addr < 0x2000 {
+            let bank = (self.register >> 4) as usize % self.chr_banks;
+            Some(bank * 0x2000 + addr as usize)
+        } else {
+            None
+        }
+    }
+
+    fn ppu_map_write(&mut self, _addr: u16, _value: u8) -> Option<usize> {
+        None
+    }
+}
@@ -0,0 +1,140 @@
+use crate::apu::expansion::namco_163::Namco163Audio;
+use crate::apu::expansion::ExpansionOutput;
+use crate::bus::mappers::Mapper;
+use crate::nes_parser::Mirroring;
+
+// Namco 129/163
+pub(crate) struct Mapper19 {
+    // In 8Kib units
+    prg_banks: usize,
+    // In 1Kib units
+    chr_banks: usize,
+    prg_bank: [u8; 3],
+    chr_bank: [u8; 8],
+    // Values of $E0 and up pick one of the console's nametables, anything else CHR ROM
+    nametable_bank: [u8; 4],
+    // 15 bit, counts up to $7FFF and then raises the IRQ
+    irq_counter: u16,
+    irq_enabled: bool,
+    irq_pending: bool,
+    audio: Namco163Audio,
+}
+
+impl Mapper19 {
+    pub fn new(prg_size: u8, chr_size: u8) -> Self {
+        Mapper19 {
+            prg_banks: prg_size as usize * 2,
+            chr_banks: chr_size.max(1) as usize * 8,
+            prg_bank: [0; 3],
+            chr_bank: [0; 8],
+            nametable_bank: [0xE0, 0xE1, 0xE0, 0xE1],
+            irq_counter: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            audio: Namco163Audio::new(),
+        }
+    }
+
+    fn prg_offset(&self, bank: usize, addr: u16) -> usize {
+        (bank % self.prg_banks) * 0x2000 + (addr & 0x1FFF) as usize
+    }
+}
+
+impl Mapper for Mapper19 {
+    fn cpu_map_read(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xDFFF => {
+                let bank = self.prg_bank[((addr - 0x8000) >> 13) as usize] & 0x3F;
+                Some(self.prg_offset(bank as usize, addr))
+            }
+            0xE000..=0xFFFF => Some(self.prg_offset(self.prg_banks - 1, addr)),
+            _ => None,
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<usize> {
+        match addr {
+            0x4800..=0x4FFF => self.audio.write_data(value),
+            0x5000..=0x57FF => {
+                self.irq_counter = (self.irq_counter & 0x7F00) | value as u16;
+                self.irq_pending = false;
+            }
+            0x5800..=0x5FFF => {
+                self.irq_counter = (self.irq_counter & 0xFF) | (((value & 0x7F) as u16) << 8);
+                self.irq_enabled = value & 0x80 != 0;
+                self.irq_pending = false;
+            }
+            0x8000..=0xBFFF => self.chr_bank[((addr - 0x8000) >> 11) as usize] = value,
+            0xC000..=0xDFFF => self.nametable_bank[((addr - 0xC000) >> 11) as usize] = value,
+            0xE000..=0xE7FF => {
+                self.prg_bank[0] = value & 0x3F;
+                self.audio.set_disabled(value & 0x40 != 0);
+            }
+            0xE800..=0xEFFF => self.prg_bank[1] = value & 0x3F,
+            0xF000..=0xF7FF => self.prg_bank[2] = value & 0x3F,
+            0xF800..=0xFFFF => self.audio.write_address(value),
+            _ => (),
+        }
+        None
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> Option<usize> {
+        if addr < 0x2000 {
+            let bank = self.chr_bank[(addr >> 10) as usize] as usize % self.chr_banks;
+            Some(bank * 0x400 + (addr & 0x3FF) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn ppu_map_write(&mut self, _addr: u16, _value: u8) -> Option<usize> {
+        None
+    }
+
+    // Only the console's own nametables are supported, arranged like one of the usual mirrorings
+    fn mirroring(&self) -> Option<Mirroring> {
+        let pages = [
+            self.nametable_bank[0] & 1,
+            self.nametable_bank[1] & 1,
+            self.nametable_bank[2] & 1,
+            self.nametable_bank[3] & 1,
+        ];
+        match pages {
+            [0, 1, 0, 1] => Some(Mirroring::Vertical),
+            [0, 0, 1, 1] => Some(Mirroring::Horizontal),
+            [0, 0, 0, 0] => Some(Mirroring::SingleScreenA),
+            [1, 1, 1, 1] => Some(Mirroring::SingleScreenB),
+            _ => None,
+        }
+    }
+
+    fn irq_line(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn cpu_read_register(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x4800..=0x4FFF => Some(self.audio.read_data()),
+            0x5000..=0x57FF => Some(self.irq_counter as u8),
+            0x5800..=0x5FFF => Some((self.irq_counter >> 8) as u8 | (self.irq_enabled as u8) << 7),
+            _ => None,
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        if self.irq_enabled && self.irq_counter < 0x7FFF {
+            self.irq_counter += 1;
+            if self.irq_counter == 0x7FFF {
+                self.irq_pending = true;
+            }
+        }
+        self.audio.clock();
+    }
+
+    fn audio_output(&self) -> ExpansionOutput {
+        ExpansionOutput {
+            namco_163: self.audio.output(),
+            ..Default::default()
+        }
+    }
+}
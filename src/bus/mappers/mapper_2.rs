@@ -0,0 +1,53 @@
+use crate::bus::mappers::Mapper;
+
+// UxROM, a switchable 16Kib bank at $8000 and the last one fixed at $C000
+pub(crate) struct Mapper2 {
+    // In 16Kib units
+    prg_banks: usize,
+    chr_ram: bool,
+    prg_bank: u8,
+}
+
+impl Mapper2 {
+    pub fn new(prg_size: u8, chr_size: u8) -> Self {
+        Mapper2 {
+            prg_banks: prg_size.max(1) as usize,
+            chr_ram: chr_size == 0,
+            prg_bank: 0,
+        }
+    }
+}
+
+impl Mapper for Mapper2 {
+    fn cpu_map_read(&self, addr: u16) -> Option<usize> {
+        let bank = match addr {
+            0x8000..=0xBFFF => self.prg_bank as usize % self.prg_banks,
+            0xC000..=0xFFFF => self.prg_banks - 1,
+            _ => return None,
+        };
+        Some(bank * 0x4000 + (addr & 0x3FFF) as usize)
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<usize> {
+        if addr & 0x8000 != 0 {
+            self.prg_bank = value;
+        }
+        None
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> Option<usize> {
+        if addr < 0x2000 {
+            Some(addr as usize)
+        } else {
+            None
+        }
+    }
+
+    fn ppu_map_write(&mut self, addr: u16, _value: u8) -> Option<usize> {
+        if self.chr_ram {
+            self.ppu_map_read(addr)
+        } else {
+            None
+        }
+    }
+}
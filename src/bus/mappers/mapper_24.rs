@@ -0,0 +1,138 @@
+use crate::apu::expansion::vrc6::Vrc6Audio;
+use crate::apu::expansion::ExpansionOutput;
+use crate::bus::mappers::vrc_irq::VrcIrq;
+use crate::bus::mappers::Mapper;
+use crate::nes_parser::Mirroring;
+
+// Konami VRC6, mapper 24 and mapper 26 which has A0 and A1 swapped
+pub(crate) struct Mapper24 {
+    swap_lines: bool,
+    // In 8Kib units
+    prg_banks: usize,
+    // In 1Kib units, CHR RAM if the cartridge had no CHR ROM
+    chr_banks: usize,
+    chr_ram: bool,
+    prg_bank_16k: u8,
+    prg_bank_8k: u8,
+    chr_bank: [u8; 8],
+    mirroring: Mirroring,
+    prg_ram_enabled: bool,
+    irq: VrcIrq,
+    audio: Vrc6Audio,
+}
+
+impl Mapper24 {
+    pub fn new(prg_size: u8, chr_size: u8, swap_lines: bool) -> Self {
+        Mapper24 {
+            swap_lines,
+            prg_banks: prg_size as usize * 2,
+            chr_banks: chr_size.max(1) as usize * 8,
+            chr_ram: chr_size == 0,
+            prg_bank_16k: 0,
+            prg_bank_8k: 0,
+            chr_bank: [0; 8],
+            mirroring: Mirroring::Vertical,
+            prg_ram_enabled: false,
+            irq: VrcIrq::default(),
+            audio: Vrc6Audio::default(),
+        }
+    }
+
+    fn prg_offset(&self, bank: usize, addr: u16) -> usize {
+        (bank % self.prg_banks) * 0x2000 + (addr & 0x1FFF) as usize
+    }
+}
+
+impl Mapper for Mapper24 {
+    fn cpu_map_read(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xBFFF => {
+                let bank = self.prg_bank_16k as usize * 2 + ((addr >> 13) & 1) as usize;
+                Some(self.prg_offset(bank, addr))
+            }
+            0xC000..=0xDFFF => Some(self.prg_offset(self.prg_bank_8k as usize, addr)),
+            0xE000..=0xFFFF => Some(self.prg_offset(self.prg_banks - 1, addr)),
+            _ => None,
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<usize> {
+        if addr < 0x8000 {
+            return None;
+        }
+
+        let addr = if self.swap_lines {
+            (addr & !3) | ((addr & 1) << 1) | ((addr >> 1) & 1)
+        } else {
+            addr
+        };
+        let reg = (addr & 3) as usize;
+
+        match (addr & 0xF000, reg) {
+            (0x8000, _) => self.prg_bank_16k = value & 0x0F,
+            (0xB000, 3) => {
+                // Only the common 1Kib CHR banking mode is supported
+                self.mirroring = match (value >> 2) & 3 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::SingleScreenA,
+                    _ => Mirroring::SingleScreenB,
+                };
+                self.prg_ram_enabled = value & 0x80 != 0;
+            }
+            (0x9000, _) | (0xA000, _) | (0xB000, _) => self.audio.write(addr, value),
+            (0xC000, _) => self.prg_bank_8k = value & 0x1F,
+            (0xD000, _) => self.chr_bank[reg] = value,
+            (0xE000, _) => self.chr_bank[4 + reg] = value,
+            (0xF000, 0) => self.irq.write_latch(value),
+            (0xF000, 1) => self.irq.write_control(value),
+            (0xF000, 2) => self.irq.acknowledge(),
+            _ => (),
+        }
+        None
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> Option<usize> {
+        if addr < 0x2000 {
+            let bank = self.chr_bank[(addr >> 10) as usize] as usize % self.chr_banks;
+            Some(bank * 0x400 + (addr & 0x3FF) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn ppu_map_write(&mut self, addr: u16, _value: u8) -> Option<usize> {
+        if self.chr_ram {
+            self.ppu_map_read(addr)
+        } else {
+            None
+        }
+    }
+
+    fn prg_ram_map_read(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled => Some((addr & 0x1FFF) as usize),
+            _ => None,
+        }
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        Some(self.mirroring)
+    }
+
+    fn irq_line(&self) -> bool {
+        self.irq.pending
+    }
+
+    fn cpu_clock(&mut self) {
+        self.irq.clock();
+        self.audio.clock();
+    }
+
+    fn audio_output(&self) -> ExpansionOutput {
+        ExpansionOutput {
+            vrc6: self.audio.output(),
+            ..Default::default()
+        }
+    }
+}
@@ -7,11 +7,11 @@ pub(crate) struct Mapper3 {
 }
 
 impl Mapper for Mapper3 {
-    fn cpu_map_read(&self, addr: u16) -> Option<u16> {
+    fn cpu_map_read(&self, addr: u16) -> Option<usize> {
         if addr & 0x8000 != 0 {
             match self.prg_banks {
-                1 => Some(addr & 0x3FFF),
-                2 => Some(addr & 0x7FFF),
+                1 => Some((addr & 0x3FFF) as usize),
+                2 => Some((addr & 0x7FFF) as usize),
                 _ => None,
             }
         } else {
@@ -19,20 +19,24 @@ impl Mapper for Mapper3 {
         }
     }
 
-    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<u16> {
+    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<usize> {
         if addr & 0x8000 != 0 {
             self.current_chrbank = value & 3;
-            Some(addr)
+            Some(addr as usize)
         } else {
             None
         }
     }
 
-    fn ppu_map_read(&self, addr: u16) -> Option<u16> {
-        todo!("PPU read")
+    fn ppu_map_read(&mut self, addr: u16) -> Option<usize> {
+        if addr < 0x2000 {
+            Some(self.current_chrbank as usize * 0x2000 + addr as usize)
+        } else {
+            None
+        }
     }
 
-    fn ppu_map_write(&mut self, addr: u16, value: u8) -> Option<u16> {
-        todo!("PPU write")
+    fn ppu_map_write(&mut self, _addr: u16, _value: u8) -> Option<usize> {
+        None
     }
 }
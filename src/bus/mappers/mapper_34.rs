@@ -0,0 +1,66 @@
+use crate::bus::mappers::Mapper;
+
+// Two unrelated boards share this number. BNROM switches 32Kib PRG banks through
+// $8000-$FFFF and has CHR RAM, the NINA-001 has its registers at $7FFD-$7FFF on top
+// of the PRG RAM and two 4Kib CHR ROM banks
+pub(crate) struct Mapper34 {
+    // In 32Kib units
+    prg_banks: usize,
+    // In 4Kib units
+    chr_banks: usize,
+    nina: bool,
+    prg_bank: u8,
+    chr_bank: [u8; 2],
+}
+
+impl Mapper34 {
+    pub fn new(prg_size: u8, chr_size: u8, submapper: u8) -> Self {
+        Mapper34 {
+            prg_banks: (prg_size as usize / 2).max(1),
+            chr_banks: chr_size.max(1) as usize * 2,
+            // Old headers don't say, but only the NINA-001 has CHR ROM
+            nina: submapper == 1 || (submapper == 0 && chr_size > 0),
+            prg_bank: 0,
+            chr_bank: [0, 1],
+        }
+    }
+}
+
+impl Mapper for Mapper34 {
+    fn cpu_map_read(&self, addr: u16) -> Option<usize> {
+        if addr & 0x8000 != 0 {
+            let bank = self.prg_bank as usize % self.prg_banks;
+            Some(bank * 0x8000 + (addr & 0x7FFF) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<usize> {
+        match addr {
+            0x7FFD if self.nina => self.prg_bank = value & 1,
+            0x7FFE if self.nina => self.chr_bank[0] = value & 0x0F,
+            0x7FFF if self.nina => self.chr_bank[1] = value & 0x0F,
+            0x8000..=0xFFFF if !self.nina => self.prg_bank = value,
+            _ => (),
+        }
+        None
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> Option<usize> {
+        if addr < 0x2000 {
+            let bank = self.chr_bank[(addr >> 12) as usize] as usize % self.chr_banks;
+            Some(bank * 0x1000 + (addr & 0xFFF) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn ppu_map_write(&mut self, addr: u16, _value: u8) -> Option<usize> {
+        if self.nina {
+            None
+        } else {
+            self.ppu_map_read(addr)
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use crate::bus::mappers::Mapper;
+use crate::nes_parser::Mirroring;
+
+// PPU A12 has to stay low for this many CPU cycles before a rise clocks the counter,
+// so the back and forth of 8x16 sprite fetches doesn't count as a scanline
+const A12_LOW_CYCLES: usize = 3;
+
+// Nintendo MMC3, and the MMC6 which only differs in its PRG RAM
+pub(crate) struct Mapper4 {
+    // In 8Kib units
+    prg_banks: usize,
+    // In 1Kib units, CHR RAM if the cartridge had no CHR ROM
+    chr_banks: usize,
+    chr_ram: bool,
+    mmc6: bool,
+    // Four screen boards wire their own nametables and ignore the mirroring register
+    four_screen: bool,
+    // Bits 0-2 pick the register written by $8001, bit 6 swaps the PRG windows
+    // and bit 7 the CHR ones. On the MMC6 bit 5 enables its RAM
+    bank_select: u8,
+    // R0-R1 are 2Kib CHR banks, R2-R5 1Kib CHR banks, R6-R7 PRG banks
+    registers: [u8; 8],
+    mirroring: Mirroring,
+    // MMC3: bit 7 enables PRG RAM and bit 6 protects it from writes
+    // MMC6: bits 7/6 allow reads/writes to the upper 512 bytes, bits 5/4 to the lower
+    prg_ram_protect: u8,
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+    a12: bool,
+    a12_low_since: usize,
+    cycles: usize,
+}
+
+impl Mapper4 {
+    pub fn new(prg_size: u8, chr_size: u8, mmc6: bool, four_screen: bool) -> Self {
+        Mapper4 {
+            prg_banks: prg_size as usize * 2,
+            chr_banks: chr_size.max(1) as usize * 8,
+            chr_ram: chr_size == 0,
+            mmc6,
+            four_screen,
+            bank_select: 0,
+            registers: [0, 2, 4, 5, 6, 7, 0, 1],
+            mirroring: Mirroring::Vertical,
+            // Not every game bothers enabling the RAM before using it
+            prg_ram_protect: 0x80,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            a12: false,
+            a12_low_since: 0,
+            cycles: 0,
+        }
+    }
+
+    fn prg_offset(&self, bank: usize, addr: u16) -> usize {
+        (bank % self.prg_banks) * 0x2000 + (addr & 0x1FFF) as usize
+    }
+
+    // The counter is clocked by rising edges of PPU A12, once per scanline when the
+    // background and sprites use different pattern tables
+    fn clock_a12(&mut self, addr: u16) {
+        let a12 = addr & 0x1000 != 0;
+        if a12 && !self.a12 && self.cycles - self.a12_low_since >= A12_LOW_CYCLES {
+            self.clock_counter();
+        } else if !a12 && self.a12 {
+            self.a12_low_since = self.cycles;
+        }
+        self.a12 = a12;
+    }
+
+    fn clock_counter(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    // The MMC6's 1Kib of RAM sits at $7000-$7FFF, each half has its own enable bits
+    fn mmc6_ram_map(&self, addr: u16, enable_shift: u8) -> Option<usize> {
+        if !(0x7000..=0x7FFF).contains(&addr) || self.bank_select & 0x20 == 0 {
+            return None;
+        }
+
+        let upper = addr & 0x200 != 0;
+        let shift = if upper {
+            enable_shift + 2
+        } else {
+            enable_shift
+        };
+        if self.prg_ram_protect & (1 << shift) != 0 {
+            Some((addr & 0x3FF) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+impl Mapper for Mapper4 {
+    fn cpu_map_read(&self, addr: u16) -> Option<usize> {
+        let second_last = self.prg_banks - 2;
+        let swapped = self.bank_select & 0x40 != 0;
+        let bank = match addr {
+            0x8000..=0x9FFF if swapped => second_last,
+            0x8000..=0x9FFF => self.registers[6] as usize,
+            0xA000..=0xBFFF => self.registers[7] as usize,
+            0xC000..=0xDFFF if swapped => self.registers[6] as usize,
+            0xC000..=0xDFFF => second_last,
+            0xE000..=0xFFFF => self.prg_banks - 1,
+            _ => return None,
+        };
+        Some(self.prg_offset(bank & 0x3F, addr))
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<usize> {
+        match (addr, addr & 1) {
+            (0x8000..=0x9FFF, 0) => self.bank_select = value,
+            (0x8000..=0x9FFF, _) => self.registers[(self.bank_select & 7) as usize] = value,
+            (0xA000..=0xBFFF, 0) => {
+                self.mirroring = if value & 1 == 0 {
+                    Mirroring::Vertical
+                } else {
+                    Mirroring::Horizontal
+                }
+            }
+            // The MMC6 ignores the protect bits until its RAM is enabled
+            (0xA000..=0xBFFF, _) if self.mmc6 && self.bank_select & 0x20 == 0 => (),
+            (0xA000..=0xBFFF, _) => self.prg_ram_protect = value,
+            (0xC000..=0xDFFF, 0) => self.irq_latch = value,
+            (0xC000..=0xDFFF, _) => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            (0xE000..=0xFFFF, 0) => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            (0xE000..=0xFFFF, _) => self.irq_enabled = true,
+            _ => (),
+        }
+        None
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> Option<usize> {
+        if addr >= 0x2000 {
+            return None;
+        }
+        self.clock_a12(addr);
+
+        // Inverted CHR puts the 2Kib banks at $1000 instead of $0000
+        let addr = if self.bank_select & 0x80 != 0 {
+            addr ^ 0x1000
+        } else {
+            addr
+        };
+        let bank = match addr {
+            0x0000..=0x07FF => (self.registers[0] & !1) as usize | ((addr >> 10) & 1) as usize,
+            0x0800..=0x0FFF => (self.registers[1] & !1) as usize | ((addr >> 10) & 1) as usize,
+            _ => self.registers[2 + ((addr - 0x1000) >> 10) as usize] as usize,
+        };
+        Some((bank % self.chr_banks) * 0x400 + (addr & 0x3FF) as usize)
+    }
+
+    fn ppu_map_write(&mut self, addr: u16, _value: u8) -> Option<usize> {
+        let mapped = self.ppu_map_read(addr);
+        if self.chr_ram {
+            mapped
+        } else {
+            None
+        }
+    }
+
+    fn prg_ram_map_read(&self, addr: u16) -> Option<usize> {
+        if self.mmc6 {
+            return self.mmc6_ram_map(addr, 5);
+        }
+
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_protect & 0x80 != 0 => Some((addr & 0x1FFF) as usize),
+            _ => None,
+        }
+    }
+
+    fn prg_ram_map_write(&mut self, addr: u16) -> Option<usize> {
+        if self.mmc6 {
+            return self.mmc6_ram_map(addr, 4);
+        }
+
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_protect & 0xC0 == 0x80 => {
+                Some((addr & 0x1FFF) as usize)
+            }
+            _ => None,
+        }
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        if self.four_screen {
+            None
+        } else {
+            Some(self.mirroring)
+        }
+    }
+
+    fn irq_line(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn cpu_clock(&mut self) {
+        self.cycles += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_irq_latch(latch: u8) -> Mapper4 {
+        let mut mapper = Mapper4::new(8, 8, false, false);
+        mapper.cpu_map_write(0xC000, latch);
+        mapper.cpu_map_write(0xC001, 0);
+        mapper.cpu_map_write(0xE001, 0);
+        mapper
+    }
+
+    #[test]
+    fn irq_fires_when_the_counter_reaches_zero() {
+        let mut mapper = with_irq_latch(2);
+        // The first clock only reloads the counter
+        mapper.clock_counter();
+        mapper.clock_counter();
+        assert!(!mapper.irq_line());
+        mapper.clock_counter();
+        assert!(mapper.irq_line());
+
+        // Acknowledged by $E000, then the counter reloads from the latch again
+        mapper.cpu_map_write(0xE000, 0);
+        mapper.cpu_map_write(0xE001, 0);
+        mapper.clock_counter();
+        mapper.clock_counter();
+        assert!(!mapper.irq_line());
+        mapper.clock_counter();
+        assert!(mapper.irq_line());
+    }
+
+    #[test]
+    fn irq_fires_every_clock_with_a_zero_latch() {
+        let mut mapper = with_irq_latch(0);
+        for _ in 0..3 {
+            mapper.clock_counter();
+            assert!(mapper.irq_line());
+            mapper.cpu_map_write(0xE000, 0);
+            mapper.cpu_map_write(0xE001, 0);
+        }
+    }
+
+    #[test]
+    fn a12_has_to_stay_low_before_a_rise_counts() {
+        let mut mapper = with_irq_latch(1);
+        let a12_low_then_high = |mapper: &mut Mapper4, cycles| {
+            mapper.ppu_map_read(0x0000);
+            for _ in 0..cycles {
+                mapper.cpu_clock();
+            }
+            mapper.ppu_map_read(0x1000);
+        };
+
+        a12_low_then_high(&mut mapper, A12_LOW_CYCLES);
+        assert_eq!(mapper.irq_counter, 1);
+        a12_low_then_high(&mut mapper, 1);
+        assert!(!mapper.irq_line());
+        a12_low_then_high(&mut mapper, A12_LOW_CYCLES);
+        assert!(mapper.irq_line());
+    }
+}
@@ -0,0 +1,289 @@
+use crate::apu::expansion::mmc5::Mmc5Audio;
+use crate::apu::expansion::ExpansionOutput;
+use crate::bus::mappers::Mapper;
+use crate::nes_parser::Mirroring;
+
+// Pattern table reads the PPU makes on a rendering line: 64 for the background, 16 for
+// sprites and 4 to prefetch the next line's first two tiles
+const FETCHES_PER_SCANLINE: usize = 84;
+const SPRITE_FETCHES: std::ops::Range<usize> = 64..80;
+// The PPU never goes this long without a pattern fetch while it's rendering
+const IDLE_CYCLES: usize = 10;
+
+// Nintendo MMC5, found on the ExROM boards. Its extended nametable modes (ExRAM or fill
+// nametables, extended attributes) and the vertical split aren't supported
+pub(crate) struct Mapper5 {
+    // In 8Kib units
+    prg_banks: usize,
+    prg_ram_banks: usize,
+    // In 1Kib units, CHR RAM if the cartridge had no CHR ROM
+    chr_banks: usize,
+    chr_ram: bool,
+    prg_mode: u8,
+    chr_mode: u8,
+    // $5102 and $5103, RAM is only writable when they are 2 and 1
+    prg_ram_protect: [u8; 2],
+    exram_mode: u8,
+    mirroring: Mirroring,
+    // $5113 for $6000 first, bit 7 of $5114-$5116 selects ROM instead of RAM
+    prg_registers: [u8; 5],
+    // $5120-$5127 then $5128-$512B, with $5130's upper bits
+    chr_registers: [u16; 12],
+    chr_upper: u16,
+    last_chr_set_b: bool,
+    // Snooped from PPUCTRL and PPUMASK
+    sprites_8x16: bool,
+    rendering: bool,
+    exram: Vec<u8>,
+    multiplicands: [u8; 2],
+    // There's no A12 to watch, scanlines are counted from the number of pattern fetches
+    line_fetches: usize,
+    idle_cycles: usize,
+    in_frame: bool,
+    scanline: u8,
+    irq_scanline: u8,
+    irq_enabled: bool,
+    irq_pending: bool,
+    audio: Mmc5Audio,
+}
+
+impl Mapper5 {
+    pub fn new(prg_size: u8, chr_size: u8, prg_ram_size: usize) -> Self {
+        Mapper5 {
+            prg_banks: prg_size as usize * 2,
+            prg_ram_banks: (prg_ram_size / 0x2000).max(1),
+            chr_banks: chr_size.max(1) as usize * 8,
+            chr_ram: chr_size == 0,
+            prg_mode: 3,
+            chr_mode: 0,
+            prg_ram_protect: [0; 2],
+            exram_mode: 0,
+            mirroring: Mirroring::Vertical,
+            prg_registers: [0, 0, 0, 0, 0xFF],
+            chr_registers: [0; 12],
+            chr_upper: 0,
+            last_chr_set_b: false,
+            sprites_8x16: false,
+            rendering: false,
+            exram: vec![0; 0x400],
+            multiplicands: [0xFF; 2],
+            line_fetches: 0,
+            idle_cycles: 0,
+            in_frame: false,
+            scanline: 0,
+            irq_scanline: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            audio: Mmc5Audio::new(),
+        }
+    }
+
+    // Which register maps the address, and whether it points at ROM. Bigger windows
+    // ignore the low bits of their bank
+    fn prg_bank(&self, addr: u16) -> (bool, usize) {
+        let window = ((addr >> 13) & 3) as usize;
+        let (register, size) = match (self.prg_mode, addr) {
+            (_, 0x6000..=0x7FFF) => return (false, (self.prg_registers[0] & 7) as usize),
+            (0, _) => (4, 4),
+            (1, 0x8000..=0xBFFF) | (2, 0x8000..=0xBFFF) => (2, 2),
+            (1, _) => (4, 2),
+            (2, 0xC000..=0xDFFF) => (3, 1),
+            (2, _) => (4, 1),
+            _ => (1 + window, 1),
+        };
+        let value = self.prg_registers[register];
+        let bank = (value & 0x7F) as usize & !(size - 1) | (window & (size - 1));
+        // $E000-$FFFF is always ROM
+        (register == 4 || value & 0x80 != 0, bank)
+    }
+
+    fn prg_ram_map(&self, addr: u16) -> Option<usize> {
+        match self.prg_bank(addr) {
+            (false, bank) if (0x6000..=0xDFFF).contains(&addr) => {
+                Some((bank % self.prg_ram_banks) * 0x2000 + (addr & 0x1FFF) as usize)
+            }
+            _ => None,
+        }
+    }
+
+    // Set A is used for sprites and set B for the background when sprites are 8x16,
+    // outside of rendering the set written last wins
+    fn chr_set_b(&self) -> bool {
+        if !self.sprites_8x16 {
+            false
+        } else if self.rendering {
+            !SPRITE_FETCHES.contains(&self.line_fetches)
+        } else {
+            self.last_chr_set_b
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let size = 8 >> (self.chr_mode & 3);
+        // Set B only has 4Kib worth of registers, mirrored in both pattern tables
+        let set_b = self.chr_set_b();
+        let window = if set_b { addr | 0x1000 } else { addr };
+        let register = ((window >> 10) as usize / size + 1) * size - 1;
+        let bank = if set_b {
+            self.chr_registers[register + 4]
+        } else {
+            self.chr_registers[register]
+        };
+        (bank as usize % (self.chr_banks / size)) * size * 0x400 + addr as usize % (size * 0x400)
+    }
+
+    // The first scanline of a frame only starts it, the others count up to $5203
+    fn clock_scanline(&mut self) {
+        if !self.in_frame {
+            self.in_frame = true;
+            self.scanline = 0;
+            self.irq_pending = false;
+        } else {
+            self.scanline = self.scanline.wrapping_add(1);
+            if self.scanline == self.irq_scanline {
+                self.irq_pending = true;
+            }
+        }
+    }
+
+    fn write_exram(&mut self, addr: u16, value: u8) {
+        let index = (addr - 0x5C00) as usize;
+        match self.exram_mode {
+            // The nametable modes only take writes while the PPU is rendering
+            0 | 1 if self.in_frame => self.exram[index] = value,
+            0 | 1 => self.exram[index] = 0,
+            2 => self.exram[index] = value,
+            _ => (),
+        }
+    }
+}
+
+impl Mapper for Mapper5 {
+    fn cpu_map_read(&self, addr: u16) -> Option<usize> {
+        match (addr, self.prg_bank(addr)) {
+            (0x8000..=0xFFFF, (true, bank)) => {
+                Some((bank % self.prg_banks) * 0x2000 + (addr & 0x1FFF) as usize)
+            }
+            _ => None,
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<usize> {
+        match addr {
+            0x5000..=0x5015 => self.audio.write(addr, value),
+            0x5100 => self.prg_mode = value & 3,
+            0x5101 => self.chr_mode = value & 3,
+            0x5102 | 0x5103 => self.prg_ram_protect[(addr - 0x5102) as usize] = value & 3,
+            0x5104 => self.exram_mode = value & 3,
+            // Only the layouts that fit the console's two nametables, the rest keep the
+            // previous mirroring
+            0x5105 => {
+                self.mirroring = match value {
+                    0x44 => Mirroring::Vertical,
+                    0x50 => Mirroring::Horizontal,
+                    0x00 => Mirroring::SingleScreenA,
+                    0x55 => Mirroring::SingleScreenB,
+                    _ => self.mirroring,
+                }
+            }
+            0x5113..=0x5117 => self.prg_registers[(addr - 0x5113) as usize] = value,
+            0x5120..=0x512B => {
+                self.chr_registers[(addr - 0x5120) as usize] = self.chr_upper | value as u16;
+                self.last_chr_set_b = addr >= 0x5128;
+            }
+            0x5130 => self.chr_upper = ((value & 3) as u16) << 8,
+            0x5203 => self.irq_scanline = value,
+            0x5204 => self.irq_enabled = value & 0x80 != 0,
+            0x5205 | 0x5206 => self.multiplicands[(addr - 0x5205) as usize] = value,
+            0x5C00..=0x5FFF => self.write_exram(addr, value),
+            _ => (),
+        }
+        None
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> Option<usize> {
+        if addr >= 0x2000 {
+            return None;
+        }
+
+        let mapped = self.chr_offset(addr);
+        if self.rendering {
+            self.idle_cycles = 0;
+            self.line_fetches += 1;
+            if self.line_fetches == FETCHES_PER_SCANLINE {
+                self.line_fetches = 0;
+                self.clock_scanline();
+            }
+        }
+        Some(mapped)
+    }
+
+    fn ppu_map_write(&mut self, addr: u16, _value: u8) -> Option<usize> {
+        if self.chr_ram && addr < 0x2000 {
+            Some(self.chr_offset(addr))
+        } else {
+            None
+        }
+    }
+
+    fn ppu_register_write(&mut self, addr: u16, value: u8) {
+        match addr & 7 {
+            0 => self.sprites_8x16 = value & 0x20 != 0,
+            1 => self.rendering = value & 0x18 != 0,
+            _ => (),
+        }
+    }
+
+    fn prg_ram_map_read(&self, addr: u16) -> Option<usize> {
+        self.prg_ram_map(addr)
+    }
+
+    fn prg_ram_map_write(&mut self, addr: u16) -> Option<usize> {
+        if self.prg_ram_protect == [2, 1] {
+            self.prg_ram_map(addr)
+        } else {
+            None
+        }
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        Some(self.mirroring)
+    }
+
+    fn irq_line(&self) -> bool {
+        self.irq_pending && self.irq_enabled
+    }
+
+    fn cpu_read_register(&mut self, addr: u16) -> Option<u8> {
+        let product = self.multiplicands[0] as u16 * self.multiplicands[1] as u16;
+        match addr {
+            0x5015 => Some(self.audio.read_status()),
+            0x5204 => {
+                let status = (self.irq_pending as u8) << 7 | (self.in_frame as u8) << 6;
+                self.irq_pending = false;
+                Some(status)
+            }
+            0x5205 => Some(product as u8),
+            0x5206 => Some((product >> 8) as u8),
+            0x5C00..=0x5FFF if self.exram_mode >= 2 => Some(self.exram[(addr - 0x5C00) as usize]),
+            _ => None,
+        }
+    }
+
+    // The frame is over once the PPU stops fetching, in vblank or with rendering off
+    fn cpu_clock(&mut self) {
+        self.idle_cycles += 1;
+        if self.idle_cycles == IDLE_CYCLES {
+            self.in_frame = false;
+            self.line_fetches = 0;
+        }
+        self.audio.clock();
+    }
+
+    fn audio_output(&self) -> ExpansionOutput {
+        ExpansionOutput {
+            mmc5: self.audio.output(),
+            ..Default::default()
+        }
+    }
+}
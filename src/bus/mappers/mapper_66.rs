@@ -0,0 +1,51 @@
+use crate::bus::mappers::Mapper;
+
+// GxROM, like Color Dreams but the PRG bank is in bits 4-5 and the CHR bank in bits 0-1
+pub(crate) struct Mapper66 {
+    // In 32Kib units
+    prg_banks: usize,
+    // In 8Kib units
+    chr_banks: usize,
+    register: u8,
+}
+
+impl Mapper66 {
+    pub fn new(prg_size: u8, chr_size: u8) -> Self {
+        Mapper66 {
+            prg_banks: (prg_size as usize / 2).max(1),
+            chr_banks: chr_size.max(1) as usize,
+            register: 0,
+        }
+    }
+}
+
+impl Mapper for Mapper66 {
+    fn cpu_map_read(&self, addr: u16) -> Option<usize> {
+        if addr & 0x8000 != 0 {
+            let bank = ((self.register >> 4) & 3) as usize % self.prg_banks;
+            Some(bank * 0x8000 + (addr & 0x7FFF) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<usize> {
+        if addr & 0x8000 != 0 {
+            self.register = value;
+        }
+        None
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> Option<usize> {
+        if addr < 0x2000 {
+            let bank = (self.register & 3) as usize % self.chr_banks;
+            Some(bank * 0x2000 + addr as usize)
+        } else {
+            None
+        }
+    }
+
+    fn ppu_map_write(&mut self, _addr: u16, _value: u8) -> Option<usize> {
+        None
+    }
+}
@@ -0,0 +1,146 @@
+use crate::apu::expansion::sunsoft_5b::Sunsoft5bAudio;
+use crate::apu::expansion::ExpansionOutput;
+use crate::bus::mappers::Mapper;
+use crate::nes_parser::Mirroring;
+
+// Sunsoft FME-7, and the 5B which is the same chip with sound
+pub(crate) struct Mapper69 {
+    // In 8Kib units
+    prg_banks: usize,
+    // In 1Kib units, CHR RAM if the cartridge had no CHR ROM
+    chr_banks: usize,
+    chr_ram: bool,
+    // Selected through $8000, its parameter written through $A000
+    command: u8,
+    chr_bank: [u8; 8],
+    // $6000 first: bit 7 enables RAM, bit 6 selects RAM instead of ROM
+    prg_bank: [u8; 4],
+    mirroring: Mirroring,
+    irq_enabled: bool,
+    counter_enabled: bool,
+    counter: u16,
+    irq_pending: bool,
+    audio: Sunsoft5bAudio,
+}
+
+impl Mapper69 {
+    pub fn new(prg_size: u8, chr_size: u8) -> Self {
+        Mapper69 {
+            prg_banks: prg_size as usize * 2,
+            chr_banks: chr_size.max(1) as usize * 8,
+            chr_ram: chr_size == 0,
+            command: 0,
+            chr_bank: [0; 8],
+            prg_bank: [0; 4],
+            mirroring: Mirroring::Vertical,
+            irq_enabled: false,
+            counter_enabled: false,
+            counter: 0,
+            irq_pending: false,
+            audio: Sunsoft5bAudio::new(),
+        }
+    }
+
+    fn prg_offset(&self, bank: usize, addr: u16) -> usize {
+        (bank % self.prg_banks) * 0x2000 + (addr & 0x1FFF) as usize
+    }
+
+    fn write_parameter(&mut self, value: u8) {
+        match self.command {
+            0x0..=0x7 => self.chr_bank[self.command as usize] = value,
+            0x8..=0xB => self.prg_bank[(self.command - 8) as usize] = value,
+            0xC => {
+                self.mirroring = match value & 3 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::SingleScreenA,
+                    _ => Mirroring::SingleScreenB,
+                }
+            }
+            0xD => {
+                self.irq_enabled = value & 1 != 0;
+                self.counter_enabled = value & 0x80 != 0;
+                self.irq_pending = false;
+            }
+            0xE => self.counter = (self.counter & 0xFF00) | value as u16,
+            _ => self.counter = (self.counter & 0xFF) | ((value as u16) << 8),
+        }
+    }
+}
+
+impl Mapper for Mapper69 {
+    fn cpu_map_read(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_bank[0] & 0x40 == 0 => {
+                Some(self.prg_offset((self.prg_bank[0] & 0x3F) as usize, addr))
+            }
+            0x8000..=0xDFFF => {
+                let bank = self.prg_bank[((addr - 0x6000) >> 13) as usize] & 0x3F;
+                Some(self.prg_offset(bank as usize, addr))
+            }
+            0xE000..=0xFFFF => Some(self.prg_offset(self.prg_banks - 1, addr)),
+            _ => None,
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<usize> {
+        match addr {
+            0x8000..=0x9FFF => self.command = value & 0x0F,
+            0xA000..=0xBFFF => self.write_parameter(value),
+            0xC000..=0xDFFF => self.audio.write_address(value),
+            0xE000..=0xFFFF => self.audio.write_data(value),
+            _ => (),
+        }
+        None
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> Option<usize> {
+        if addr < 0x2000 {
+            let bank = self.chr_bank[(addr >> 10) as usize] as usize % self.chr_banks;
+            Some(bank * 0x400 + (addr & 0x3FF) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn ppu_map_write(&mut self, addr: u16, _value: u8) -> Option<usize> {
+        if self.chr_ram {
+            self.ppu_map_read(addr)
+        } else {
+            None
+        }
+    }
+
+    fn prg_ram_map_read(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_bank[0] & 0xC0 == 0xC0 => Some((addr & 0x1FFF) as usize),
+            _ => None,
+        }
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        Some(self.mirroring)
+    }
+
+    fn irq_line(&self) -> bool {
+        self.irq_pending
+    }
+
+    // The counter runs on every CPU cycle and fires when it wraps around
+    fn cpu_clock(&mut self) {
+        if self.counter_enabled {
+            self.counter = self.counter.wrapping_sub(1);
+            if self.counter == 0xFFFF && self.irq_enabled {
+                self.irq_pending = true;
+            }
+        }
+        self.audio.clock();
+    }
+
+    fn audio_output(&self) -> ExpansionOutput {
+        ExpansionOutput {
+            sunsoft_5b: self.audio.output(),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use crate::bus::mappers::Mapper;
+use crate::nes_parser::Mirroring;
+
+// AxROM, 32Kib PRG banks and a register bit picking which nametable fills the screen
+pub(crate) struct Mapper7 {
+    // In 32Kib units
+    prg_banks: usize,
+    chr_ram: bool,
+    // Bits 0-2 PRG bank, bit 4 nametable
+    register: u8,
+}
+
+impl Mapper7 {
+    pub fn new(prg_size: u8, chr_size: u8) -> Self {
+        Mapper7 {
+            prg_banks: (prg_size as usize / 2).max(1),
+            chr_ram: chr_size == 0,
+            register: 0,
+        }
+    }
+}
+
+impl Mapper for Mapper7 {
+    fn cpu_map_read(&self, addr: u16) -> Option<usize> {
+        if addr & 0x8000 != 0 {
+            let bank = (self.register & 7) as usize % self.prg_banks;
+            Some(bank * 0x8000 + (addr & 0x7FFF) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<usize> {
+        if addr & 0x8000 != 0 {
+            self.register = value;
+        }
+        None
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> Option<usize> {
+        if addr < 0x2000 {
+            Some(addr as usize)
+        } else {
+            None
+        }
+    }
+
+    fn ppu_map_write(&mut self, addr: u16, _value: u8) -> Option<usize> {
+        if self.chr_ram {
+            self.ppu_map_read(addr)
+        } else {
+            None
+        }
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        if self.register & 0x10 == 0 {
+            Some(Mirroring::SingleScreenA)
+        } else {
+            Some(Mirroring::SingleScreenB)
+        }
+    }
+}
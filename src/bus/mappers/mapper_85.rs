@@ -0,0 +1,132 @@
+use crate::apu::expansion::vrc7::Vrc7Audio;
+use crate::apu::expansion::ExpansionOutput;
+use crate::bus::mappers::vrc_irq::VrcIrq;
+use crate::bus::mappers::Mapper;
+use crate::nes_parser::Mirroring;
+
+// Konami VRC7, VRC7a boards select registers with A4 and VRC7b with A3, we take either
+pub(crate) struct Mapper85 {
+    // In 8Kib units
+    prg_banks: usize,
+    // In 1Kib units, CHR RAM if the cartridge had no CHR ROM
+    chr_banks: usize,
+    chr_ram: bool,
+    prg_bank: [u8; 3],
+    chr_bank: [u8; 8],
+    mirroring: Mirroring,
+    prg_ram_enabled: bool,
+    irq: VrcIrq,
+    audio: Vrc7Audio,
+}
+
+impl Mapper85 {
+    pub fn new(prg_size: u8, chr_size: u8) -> Self {
+        Mapper85 {
+            prg_banks: prg_size as usize * 2,
+            chr_banks: chr_size.max(1) as usize * 8,
+            chr_ram: chr_size == 0,
+            prg_bank: [0; 3],
+            chr_bank: [0; 8],
+            mirroring: Mirroring::Vertical,
+            prg_ram_enabled: false,
+            irq: VrcIrq::default(),
+            audio: Vrc7Audio::new(),
+        }
+    }
+
+    fn prg_offset(&self, bank: usize, addr: u16) -> usize {
+        (bank % self.prg_banks) * 0x2000 + (addr & 0x1FFF) as usize
+    }
+}
+
+impl Mapper for Mapper85 {
+    fn cpu_map_read(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xDFFF => {
+                let bank = self.prg_bank[((addr - 0x8000) >> 13) as usize] & 0x3F;
+                Some(self.prg_offset(bank as usize, addr))
+            }
+            0xE000..=0xFFFF => Some(self.prg_offset(self.prg_banks - 1, addr)),
+            _ => None,
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<usize> {
+        if addr < 0x8000 {
+            return None;
+        }
+
+        // Second register of each pair
+        let high = addr & 0x18 != 0;
+        match (addr & 0xF000, high) {
+            (0x8000, false) => self.prg_bank[0] = value,
+            (0x8000, true) => self.prg_bank[1] = value,
+            (0x9000, false) => self.prg_bank[2] = value,
+            (0x9000, true) if addr & 0x20 != 0 => self.audio.write_data(value),
+            (0x9000, true) => self.audio.write_address(value),
+            (0xA000..=0xD000, _) => {
+                let index = (((addr - 0xA000) >> 12) * 2) as usize + high as usize;
+                self.chr_bank[index] = value;
+            }
+            (0xE000, false) => {
+                self.mirroring = match value & 3 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::SingleScreenA,
+                    _ => Mirroring::SingleScreenB,
+                };
+                self.audio.set_silenced(value & 0x40 != 0);
+                self.prg_ram_enabled = value & 0x80 != 0;
+            }
+            (0xE000, true) => self.irq.write_latch(value),
+            (0xF000, false) => self.irq.write_control(value),
+            (0xF000, true) => self.irq.acknowledge(),
+            _ => (),
+        }
+        None
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> Option<usize> {
+        if addr < 0x2000 {
+            let bank = self.chr_bank[(addr >> 10) as usize] as usize % self.chr_banks;
+            Some(bank * 0x400 + (addr & 0x3FF) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn ppu_map_write(&mut self, addr: u16, _value: u8) -> Option<usize> {
+        if self.chr_ram {
+            self.ppu_map_read(addr)
+        } else {
+            None
+        }
+    }
+
+    fn prg_ram_map_read(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled => Some((addr & 0x1FFF) as usize),
+            _ => None,
+        }
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        Some(self.mirroring)
+    }
+
+    fn irq_line(&self) -> bool {
+        self.irq.pending
+    }
+
+    fn cpu_clock(&mut self) {
+        self.irq.clock();
+        self.audio.clock();
+    }
+
+    fn audio_output(&self) -> ExpansionOutput {
+        ExpansionOutput {
+            vrc7: self.audio.output(),
+            ..Default::default()
+        }
+    }
+}
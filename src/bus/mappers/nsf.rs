@@ -0,0 +1,188 @@
+use crate::apu::expansion::fds::FdsAudio;
+use crate::apu::expansion::mmc5::Mmc5Audio;
+use crate::apu::expansion::namco_163::Namco163Audio;
+use crate::apu::expansion::sunsoft_5b::Sunsoft5bAudio;
+use crate::apu::expansion::vrc6::Vrc6Audio;
+use crate::apu::expansion::vrc7::Vrc7Audio;
+use crate::apu::expansion::ExpansionOutput;
+use crate::bus::mappers::Mapper;
+
+// Not a real board, the NSF player's 4Kib banks at $8000-$FFFF, switched through $5FF8-$5FFF,
+// together with whatever sound chips the tune asks for
+pub(crate) struct NsfMapper {
+    pub banks: [u8; 8],
+    pub bankswitched: bool,
+    pub bank_count: usize,
+    pub vrc6: Option<Vrc6Audio>,
+    pub vrc7: Option<Vrc7Audio>,
+    pub sunsoft_5b: Option<Sunsoft5bAudio>,
+    pub namco_163: Option<Namco163Audio>,
+    pub fds: Option<FdsAudio>,
+    // FDS tunes run from RAM at $6000-$DFFF, their bank registers copy a bank into it
+    pub fds_ram: Vec<u8>,
+    pub prg: Vec<u8>,
+    pub mmc5: Option<Mmc5Audio>,
+    // MMC5 tunes also get its extra RAM and multiplier
+    pub mmc5_exram: Vec<u8>,
+    pub mmc5_multiplicands: [u8; 2],
+}
+
+impl NsfMapper {
+    // $6000-$DFFF in 4Kib windows
+    pub fn fds_load_bank(&mut self, window: usize, bank: u8) {
+        let start = (bank as usize % self.bank_count) * 0x1000;
+        self.fds_ram[window * 0x1000..(window + 1) * 0x1000]
+            .copy_from_slice(&self.prg[start..start + 0x1000]);
+    }
+}
+
+impl Mapper for NsfMapper {
+    fn cpu_map_read(&self, addr: u16) -> Option<usize> {
+        if addr & 0x8000 != 0 {
+            let bank = self.banks[((addr >> 12) & 7) as usize] as usize % self.bank_count;
+            Some(bank * 0x1000 + (addr & 0xFFF) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, value: u8) -> Option<usize> {
+        if self.bankswitched && (0x5FF8..=0x5FFF).contains(&addr) {
+            self.banks[(addr & 7) as usize] = value;
+        }
+        if self.fds.is_some() {
+            match addr {
+                0x5FF6..=0x5FFD if self.bankswitched => {
+                    self.fds_load_bank((addr - 0x5FF6) as usize, value)
+                }
+                0x6000..=0xDFFF => self.fds_ram[(addr - 0x6000) as usize] = value,
+                _ => (),
+            }
+        }
+
+        if let Some(vrc6) = &mut self.vrc6 {
+            if let 0x9000..=0xBFFF = addr {
+                vrc6.write(addr, value);
+            }
+        }
+        if let Some(vrc7) = &mut self.vrc7 {
+            match addr {
+                0x9010 => vrc7.write_address(value),
+                0x9030 => vrc7.write_data(value),
+                _ => (),
+            }
+        }
+        if let Some(sunsoft_5b) = &mut self.sunsoft_5b {
+            match addr {
+                0xC000..=0xDFFF => sunsoft_5b.write_address(value),
+                0xE000..=0xFFFF => sunsoft_5b.write_data(value),
+                _ => (),
+            }
+        }
+        if let Some(namco_163) = &mut self.namco_163 {
+            match addr {
+                0x4800..=0x4FFF => namco_163.write_data(value),
+                0xF800..=0xFFFF => namco_163.write_address(value),
+                _ => (),
+            }
+        }
+        if let Some(fds) = &mut self.fds {
+            if let 0x4040..=0x408A = addr {
+                fds.write(addr, value);
+            }
+        }
+        if let Some(mmc5) = &mut self.mmc5 {
+            match addr {
+                0x5000..=0x5015 => mmc5.write(addr, value),
+                0x5205 | 0x5206 => self.mmc5_multiplicands[(addr - 0x5205) as usize] = value,
+                0x5C00..=0x5FF5 => self.mmc5_exram[(addr - 0x5C00) as usize] = value,
+                _ => (),
+            }
+        }
+        None
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> Option<usize> {
+        if addr < 0x2000 {
+            Some(addr as usize)
+        } else {
+            None
+        }
+    }
+
+    fn ppu_map_write(&mut self, addr: u16, _value: u8) -> Option<usize> {
+        self.ppu_map_read(addr)
+    }
+
+    // FDS tunes have their own RAM there
+    fn prg_ram_map_read(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x6000..=0x7FFF if self.fds.is_none() => Some((addr & 0x1FFF) as usize),
+            _ => None,
+        }
+    }
+
+    fn cpu_read_register(&mut self, addr: u16) -> Option<u8> {
+        if let Some(namco_163) = &mut self.namco_163 {
+            if let 0x4800..=0x4FFF = addr {
+                return Some(namco_163.read_data());
+            }
+        }
+        if let Some(fds) = &self.fds {
+            if let Some(value) = fds.read(addr) {
+                return Some(value);
+            }
+            if let 0x6000..=0xDFFF = addr {
+                return Some(self.fds_ram[(addr - 0x6000) as usize]);
+            }
+        }
+
+        let mmc5 = self.mmc5.as_ref()?;
+        let product = self.mmc5_multiplicands[0] as u16 * self.mmc5_multiplicands[1] as u16;
+        match addr {
+            0x5015 => Some(mmc5.read_status()),
+            0x5205 => Some(product as u8),
+            0x5206 => Some((product >> 8) as u8),
+            0x5C00..=0x5FF5 => Some(self.mmc5_exram[(addr - 0x5C00) as usize]),
+            _ => None,
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        if let Some(vrc6) = &mut self.vrc6 {
+            vrc6.clock();
+        }
+        if let Some(vrc7) = &mut self.vrc7 {
+            vrc7.clock();
+        }
+        if let Some(sunsoft_5b) = &mut self.sunsoft_5b {
+            sunsoft_5b.clock();
+        }
+        if let Some(namco_163) = &mut self.namco_163 {
+            namco_163.clock();
+        }
+        if let Some(fds) = &mut self.fds {
+            fds.clock();
+        }
+        if let Some(mmc5) = &mut self.mmc5 {
+            mmc5.clock();
+        }
+    }
+
+    fn audio_output(&self) -> ExpansionOutput {
+        ExpansionOutput {
+            vrc6: self.vrc6.as_ref().map_or(0.0, |vrc6| vrc6.output()),
+            vrc7: self.vrc7.as_ref().map_or(0.0, |vrc7| vrc7.output()),
+            fds: self.fds.as_ref().map_or(0.0, |fds| fds.output()),
+            mmc5: self.mmc5.as_ref().map_or(0.0, |mmc5| mmc5.output()),
+            namco_163: self
+                .namco_163
+                .as_ref()
+                .map_or(0.0, |namco_163| namco_163.output()),
+            sunsoft_5b: self
+                .sunsoft_5b
+                .as_ref()
+                .map_or(0.0, |sunsoft_5b| sunsoft_5b.output()),
+        }
+    }
+}
@@ -10,6 +10,10 @@ pub mod instructions;
 
 const STACK_START_ADDR: u16 = 0x100;
 
+pub const NMI_VECTOR: u16 = 0xFFFA;
+pub const RESET_VECTOR: u16 = 0xFFFC;
+pub const IRQ_VECTOR: u16 = 0xFFFE;
+
 bitflags! {
     #[derive(Default)]
     pub struct CpuFlags: u8 {
@@ -33,6 +37,10 @@ pub struct Cpu {
     pub stack_pointer: u8,
     opcode_table: [Opcode; 256],
     bus: Bus,
+    // NMI is edge triggered, so we remember the last level we saw
+    nmi_line: bool,
+    nmi_pending: bool,
+    irq_pending: bool,
 }
 
 impl fmt::Display for Cpu {
@@ -63,12 +71,73 @@ impl CpuFlags {
 
 impl Cpu {
     pub fn execute_next(&mut self) {
+        if self.nmi_pending {
+            self.nmi_pending = false;
+            self.interrupt(NMI_VECTOR);
+        } else if self.irq_pending {
+            self.interrupt(IRQ_VECTOR);
+        } else {
+            self.execute_opcode();
+            return;
+        }
+
+        self.poll_interrupts(self.status.contains(CpuFlags::I));
+    }
+
+    fn execute_opcode(&mut self) {
         let opcode = self.opcode_table[self.bus.cpu_read(self.program_counter) as usize];
+        let i_flag = self.status.contains(CpuFlags::I);
 
+        self.bus
+            .begin_instruction(opcode.cycle_count, opcode.get_write_cycles());
         (opcode.instr.execute)(self, opcode.addresing_mode);
 
         self.bus.cycle(opcode.cycle_count);
-        self.program_counter += opcode.get_length()
+        self.program_counter += opcode.get_length();
+
+        // Interrupts are polled before the last cycle, so CLI, SEI and PLP only affect
+        // IRQs after the next instruction
+        let i_flag = match opcode.instr.name {
+            "CLI" | "SEI" | "PLP" => i_flag,
+            _ => self.status.contains(CpuFlags::I),
+        };
+        self.poll_interrupts(i_flag);
+
+        // An NMI arriving mid BRK hijacks it, BRK's pushes happen but the NMI vector is used
+        if opcode.instr.name == "BRK" && self.nmi_pending {
+            self.nmi_pending = false;
+            self.program_counter = self.bus.cpu_read_word(NMI_VECTOR);
+        }
+    }
+
+    fn poll_interrupts(&mut self, i_flag: bool) {
+        let nmi_line = self.bus.nmi_line();
+        if nmi_line && !self.nmi_line {
+            self.nmi_pending = true;
+        }
+        self.nmi_line = nmi_line;
+
+        self.irq_pending = self.bus.irq_line() && !i_flag;
+    }
+
+    // Hardware interrupt sequence, like BRK but pushes the status without B
+    fn interrupt(&mut self, vector: u16) {
+        self.stack_push_word(self.program_counter);
+        self.stack_push(((self.status | CpuFlags::BS) - CpuFlags::B).bits());
+        self.status.insert(CpuFlags::I);
+        self.program_counter = self.bus.cpu_read_word(vector);
+        self.bus.cycle(7);
+    }
+
+    // RESET goes through the interrupt sequence with the writes suppressed
+    pub fn reset(&mut self) {
+        self.bus.reset();
+        self.stack_pointer = self.stack_pointer.wrapping_sub(3);
+        self.status.insert(CpuFlags::I);
+        self.program_counter = self.bus.cpu_read_word(RESET_VECTOR);
+        self.nmi_pending = false;
+        self.irq_pending = false;
+        self.bus.cycle(7);
     }
 
     pub fn stack_push(&mut self, value: u8) {
@@ -98,16 +167,30 @@ impl Cpu {
         self.opcode_table
     }
 
+    pub fn get_bus(&self) -> &Bus {
+        &self.bus
+    }
+
+    pub fn get_bus_mut(&mut self) -> &mut Bus {
+        &mut self.bus
+    }
+
+    // Powering on runs the same RESET sequence, which is where SP ends up at $FD
     pub fn create_from_bus(bus: Bus) -> Self {
-        Self {
-            program_counter: bus.cpu_read_word(0xFFFC),
+        let mut cpu = Self {
+            program_counter: 0,
             reg_a: 0,
             reg_x: 0,
             reg_y: 0,
             status: CpuFlags::BS,
-            stack_pointer: 0xFD,
+            stack_pointer: 0,
             opcode_table: instructions::get_opcode_table(),
             bus,
-        }
+            nmi_line: false,
+            nmi_pending: false,
+            irq_pending: false,
+        };
+        cpu.reset();
+        cpu
     }
 }
@@ -78,10 +78,21 @@ impl Opcode {
             _ => 3,
         }
     }
+
+    // How many of the instruction's last cycles are writes, JSR and BRK push in the
+    // middle of theirs so they don't count
+    pub fn get_write_cycles(&self) -> u8 {
+        match (self.instr.name, self.addresing_mode) {
+            ("STA" | "STX" | "STY" | "PHA" | "PHP", _) => 1,
+            (_, AddresingMode::ACC) => 0,
+            ("ASL" | "LSR" | "ROL" | "ROR" | "INC" | "DEC", _) => 2,
+            _ => 0,
+        }
+    }
 }
 
 // Used for debugging purposes, mostly used with nestest.log
-pub fn addr_to_instr(cpu: &Cpu, addr: u16) -> String {
+pub fn addr_to_instr(cpu: &mut Cpu, addr: u16) -> String {
     let (opcode, argb, argw) = (
         cpu.bus.cpu_read(addr),
         cpu.bus.cpu_read(addr + 1),
@@ -119,27 +130,27 @@ pub fn addr_to_instr(cpu: &Cpu, addr: u16) -> String {
                     .wrapping_add((argb as i8) as i16)
                     .wrapping_add(2) as u16
             ),
-            AddresingMode::IDX => format!(
-                "(${:02X}, X) @ {:02X} = {:04X} = {:02X}",
-                argb,
-                argb.wrapping_add(cpu.reg_x),
-                cpu.bus.cpu_read_zp_word(argb.wrapping_add(cpu.reg_x)),
-                cpu.bus
-                    .cpu_read(cpu.bus.cpu_read_zp_word(argb.wrapping_add(cpu.reg_x)))
-            ),
-            AddresingMode::IDY => format!(
-                "(${:02X}),Y = {:04X} @ {:04X} = {:02X}",
-                argb,
-                cpu.bus.cpu_read_zp_word(argb),
-                cpu.bus
-                    .cpu_read_zp_word(argb)
-                    .wrapping_add(cpu.reg_y as u16),
-                cpu.bus.cpu_read(
-                    cpu.bus
-                        .cpu_read_zp_word(argb)
-                        .wrapping_add(cpu.reg_y as u16)
+            AddresingMode::IDX => {
+                let target = cpu.bus.cpu_read_zp_word(argb.wrapping_add(cpu.reg_x));
+                format!(
+                    "(${:02X}, X) @ {:02X} = {:04X} = {:02X}",
+                    argb,
+                    argb.wrapping_add(cpu.reg_x),
+                    target,
+                    cpu.bus.cpu_read(target)
                 )
-            ),
+            }
+            AddresingMode::IDY => {
+                let base = cpu.bus.cpu_read_zp_word(argb);
+                let target = base.wrapping_add(cpu.reg_y as u16);
+                format!(
+                    "(${:02X}),Y = {:04X} @ {:04X} = {:02X}",
+                    argb,
+                    base,
+                    target,
+                    cpu.bus.cpu_read(target)
+                )
+            }
         })
         .trim_end()
         .to_string()
@@ -160,7 +171,7 @@ pub fn dump_current_instruction(cpu: &mut Cpu) -> String {
     }
     s.push_str(&format!(
         " {:30} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:---,--- CYC:{}",
-        addr_to_instr(&cpu, cpu.program_counter),
+        addr_to_instr(cpu, cpu.program_counter),
         cpu.reg_a,
         cpu.reg_x,
         cpu.reg_y,
@@ -4,10 +4,6 @@ use crate::cpu::*;
 pub fn instr_lda(cpu: &mut Cpu, mode: AddresingMode) {
     let (_input, value, _cross) = read_instr_value(cpu, mode);
     cpu.reg_a = value;
-    println!(
-        "[{:04X}] LDA val = {:02X}, addr = {:04X}",
-        cpu.program_counter, value, _input
-    );
     set_nz_flags(cpu, value);
 }
 
@@ -6,6 +6,12 @@ Read-Modify-Write instructions (ASL, LSR, ROL, ROR, INC, DEC)
 According to 6502_cpu.txt the ways to handle the addressing modes are the following:
 */
 
+// The 6502 writes the unmodified value back before the result, mappers like the MMC1 notice
+fn write_modified(cpu: &mut Cpu, addr: u16, value: u8, newval: u8) {
+    cpu.bus.cpu_write(addr, value);
+    cpu.bus.cpu_write(addr, newval);
+}
+
 fn general_shift(
     cpu: &mut Cpu,
     mode: AddresingMode,
@@ -16,7 +22,7 @@ fn general_shift(
     let newval = oper(value, 1);
 
     if mode.is_input_address() {
-        cpu.bus.cpu_write(input, newval);
+        write_modified(cpu, input, value, newval);
     } else {
         cpu.reg_a = newval;
     }
@@ -43,7 +49,7 @@ pub fn instr_rol(cpu: &mut Cpu, mode: AddresingMode) {
     cpu.status.set(CpuFlags::C, value & (1u8 << 7) != 0);
 
     if mode.is_input_address() {
-        cpu.bus.cpu_write(input, newval);
+        write_modified(cpu, input, value, newval);
     } else {
         cpu.reg_a = newval;
     }
@@ -56,13 +62,9 @@ pub fn instr_ror(cpu: &mut Cpu, mode: AddresingMode) {
     let newval = (value >> 1) | ((cpu.status.contains(CpuFlags::C) as u8) << 7);
 
     cpu.status.set(CpuFlags::C, value & 1 != 0);
-    println!(
-        "{:04X}: DOING ROR ON {:02X} AND GOT {:02X}",
-        cpu.program_counter, value, newval
-    );
 
     if mode.is_input_address() {
-        cpu.bus.cpu_write(input, newval);
+        write_modified(cpu, input, value, newval);
     } else {
         cpu.reg_a = newval;
     }
@@ -74,7 +76,7 @@ pub fn instr_inc(cpu: &mut Cpu, mode: AddresingMode) {
     let (input, value, _cross) = read_instr_value(cpu, mode);
     let newval = value.wrapping_add(1);
     if mode.is_input_address() {
-        cpu.bus.cpu_write(input, newval);
+        write_modified(cpu, input, value, newval);
     } else {
         cpu.reg_a = newval;
     }
@@ -85,7 +87,7 @@ pub fn instr_dec(cpu: &mut Cpu, mode: AddresingMode) {
     let (input, value, _cross) = read_instr_value(cpu, mode);
     let newval = value.wrapping_sub(1);
     if mode.is_input_address() {
-        cpu.bus.cpu_write(input, newval);
+        write_modified(cpu, input, value, newval);
     } else {
         cpu.reg_a = newval;
     }
@@ -28,15 +28,12 @@ pub fn instr_jmp(cpu: &mut Cpu, mode: AddresingMode) {
 }
 
 pub fn instr_brk(cpu: &mut Cpu, mode: AddresingMode) {
-    cpu.program_counter += 1;
+    // BRK is followed by a padding byte which the return address skips
+    cpu.stack_push_word(cpu.program_counter.wrapping_add(2));
+    cpu.stack_push((cpu.status | CpuFlags::BS | CpuFlags::B).bits());
     cpu.status.insert(CpuFlags::I);
-    cpu.stack_push_word(cpu.program_counter);
-
-    cpu.status.insert(CpuFlags::BS);
-    cpu.stack_push(cpu.status.bits());
-    cpu.status.remove(CpuFlags::BS);
 
-    cpu.program_counter = cpu.bus.cpu_read_word(0xFFFE) - mode.get_length();
+    cpu.program_counter = cpu.bus.cpu_read_word(IRQ_VECTOR) - mode.get_length();
 }
 
 pub fn instr_rti(cpu: &mut Cpu, mode: AddresingMode) {
@@ -67,7 +67,8 @@ pub fn get_input(cpu: &mut Cpu, addresing_mode: AddresingMode) -> (u16, bool) {
             false,
         ),
         AddresingMode::IDY => {
-            add_chk_page_cross(cpu, cpu.bus.cpu_read_zp_word(argb), cpu.reg_y as u16)
+            let base = cpu.bus.cpu_read_zp_word(argb);
+            add_chk_page_cross(cpu, base, cpu.reg_y as u16)
         }
     }
 }
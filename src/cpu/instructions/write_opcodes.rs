@@ -4,19 +4,20 @@ use crate::cpu::*;
 /*
 Write instructions (STA, STX, STY)
 According to 6502_cpu.txt the ways to handle the addressing modes are the following:
+Note these must not read their target, reading memory mapped registers has side effects
 */
 
 pub fn instr_sta(cpu: &mut Cpu, mode: AddresingMode) {
-    let (input, _value, _cross) = read_instr_value(cpu, mode);
+    let (input, _cross) = get_input(cpu, mode);
     cpu.bus.cpu_write(input, cpu.reg_a);
 }
 
 pub fn instr_sty(cpu: &mut Cpu, mode: AddresingMode) {
-    let (input, _value, _cross) = read_instr_value(cpu, mode);
+    let (input, _cross) = get_input(cpu, mode);
     cpu.bus.cpu_write(input, cpu.reg_y);
 }
 
 pub fn instr_stx(cpu: &mut Cpu, mode: AddresingMode) {
-    let (input, _value, _cross) = read_instr_value(cpu, mode);
+    let (input, _cross) = get_input(cpu, mode);
     cpu.bus.cpu_write(input, cpu.reg_x);
 }
@@ -3,32 +3,339 @@ mod cpu;
 mod nes_parser;
 mod bus;
 mod ppu;
+mod apu;
+mod audio;
+mod nsf_parser;
+mod nsf_player;
 
-use crate::screen::create_window;
-use pixels::{Error, Pixels, SurfaceTexture};
-use winit::dpi::LogicalSize;
+use crate::apu::mixer::{Channel, ChannelControls, CHANNELS};
+#[cfg(feature = "audio-device")]
+use crate::audio::device::DeviceSink;
+use crate::audio::sink::{AudioSink, NullSink, TeeSink};
+use crate::audio::stems::StemWriter;
+use crate::audio::wav::WavSink;
+use crate::audio::DEFAULT_SAMPLE_RATE;
+use crate::bus::controller::Buttons;
+use crate::bus::Bus;
+use crate::cpu::Cpu;
+use crate::nes_parser::get_cartridge_from_file;
+use crate::nsf_parser::{get_nsf_from_file, is_nsf_file};
+use crate::nsf_player::NsfPlayer;
+use crate::ppu::palette::SystemPalette;
+use crate::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::screen::{create_window, draw_frame};
 use winit::event::{Event, VirtualKeyCode};
-use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::WindowBuilder;
+use winit::event_loop::ControlFlow;
 use winit_input_helper::WinitInputHelper;
-use rand::Rng;
+
+// Player 1 on the arrows, player 2 on WASD
+const KEYMAPS: [[(VirtualKeyCode, Buttons); 8]; 2] = [
+    [
+        (VirtualKeyCode::X, Buttons::A),
+        (VirtualKeyCode::Z, Buttons::B),
+        (VirtualKeyCode::RShift, Buttons::SELECT),
+        (VirtualKeyCode::Return, Buttons::START),
+        (VirtualKeyCode::Up, Buttons::UP),
+        (VirtualKeyCode::Down, Buttons::DOWN),
+        (VirtualKeyCode::Left, Buttons::LEFT),
+        (VirtualKeyCode::Right, Buttons::RIGHT),
+    ],
+    [
+        (VirtualKeyCode::H, Buttons::A),
+        (VirtualKeyCode::G, Buttons::B),
+        (VirtualKeyCode::T, Buttons::SELECT),
+        (VirtualKeyCode::Y, Buttons::START),
+        (VirtualKeyCode::W, Buttons::UP),
+        (VirtualKeyCode::S, Buttons::DOWN),
+        (VirtualKeyCode::A, Buttons::LEFT),
+        (VirtualKeyCode::D, Buttons::RIGHT),
+    ],
+];
+
+// F1-F11 mute a channel, with Shift they solo it, with Ctrl/Alt they turn it down/up
+const CHANNEL_KEYS: [VirtualKeyCode; CHANNELS.len()] = [
+    VirtualKeyCode::F1,
+    VirtualKeyCode::F2,
+    VirtualKeyCode::F3,
+    VirtualKeyCode::F4,
+    VirtualKeyCode::F5,
+    VirtualKeyCode::F6,
+    VirtualKeyCode::F7,
+    VirtualKeyCode::F8,
+    VirtualKeyCode::F9,
+    VirtualKeyCode::F10,
+    VirtualKeyCode::F11,
+];
+const VOLUME_STEP: f32 = 0.1;
+
+fn update_channel_controls(input: &WinitInputHelper, controls: &mut ChannelControls) {
+    for (key, &channel) in CHANNEL_KEYS.iter().zip(CHANNELS.iter()) {
+        if !input.key_pressed(*key) {
+            continue;
+        }
+
+        if input.held_shift() {
+            controls.set_soloed(channel, !controls.is_soloed(channel));
+        } else if input.held_control() {
+            controls.set_volume(channel, controls.get_volume(channel) - VOLUME_STEP);
+        } else if input.held_alt() {
+            controls.set_volume(channel, controls.get_volume(channel) + VOLUME_STEP);
+        } else {
+            controls.set_muted(channel, !controls.is_muted(channel));
+        }
+
+        eprintln!(
+            "{}: volume {:.1}{}{}",
+            channel.get_name(),
+            controls.get_volume(channel),
+            if controls.is_muted(channel) { ", muted" } else { "" },
+            if controls.is_soloed(channel) { ", soloed" } else { "" },
+        );
+    }
+}
+
+fn read_buttons(input: &WinitInputHelper, keymap: &[(VirtualKeyCode, Buttons)]) -> Buttons {
+    keymap
+        .iter()
+        .filter(|(key, _)| input.key_held(*key))
+        .fold(Buttons::empty(), |buttons, (_, button)| buttons | *button)
+}
+
+// What's loaded, a game cartridge or an NSF tune
+enum Program {
+    Game(Cpu),
+    Music(NsfPlayer),
+}
+
+impl Program {
+    fn load(options: &Options) -> Self {
+        if is_nsf_file(&options.rom_path) {
+            let mut player = NsfPlayer::new(get_nsf_from_file(&options.rom_path));
+            if let Some(track) = options.track {
+                player.set_song(track.saturating_sub(1));
+            }
+            print_song(&player);
+            Program::Music(player)
+        } else {
+            Program::Game(Cpu::create_from_bus(Bus::create_from_crt(get_cartridge_from_file(
+                &options.rom_path,
+            ))))
+        }
+    }
+
+    fn get_cpu(&self) -> &Cpu {
+        match self {
+            Program::Game(cpu) => cpu,
+            Program::Music(player) => player.get_cpu(),
+        }
+    }
+
+    fn get_cpu_mut(&mut self) -> &mut Cpu {
+        match self {
+            Program::Game(cpu) => cpu,
+            Program::Music(player) => player.get_cpu_mut(),
+        }
+    }
+
+    fn run_frame(&mut self) {
+        match self {
+            Program::Game(cpu) => {
+                let frame = cpu.get_bus().get_ppu().get_frame_count();
+                while cpu.get_bus().get_ppu().get_frame_count() == frame {
+                    cpu.execute_next();
+                }
+            }
+            Program::Music(player) => player.run_frame(),
+        }
+    }
+}
+
+fn print_song(player: &NsfPlayer) {
+    let nsf = player.get_nsf();
+    let song = player.get_song();
+    eprintln!(
+        "{} - {} ({}/{}){}",
+        nsf.artist,
+        nsf.name,
+        song + 1,
+        nsf.total_songs,
+        nsf.get_track_label(song)
+            .map(|label| format!(": {}", label))
+            .unwrap_or_default(),
+    );
+}
+
+fn run_frame(program: &mut Program, sink: &mut dyn AudioSink, stems: Option<&mut StemWriter>) {
+    program.run_frame();
+
+    let cpu = program.get_cpu_mut();
+    let samples = cpu.get_bus_mut().take_audio_samples();
+    sink.write_samples(&samples);
+    if let Some(stems) = stems {
+        stems.write_samples(&samples, &cpu.get_bus_mut().take_stem_samples());
+    }
+}
+
+const USAGE: &str = "Usage: nust <rom.nes|tune.nsf> [--track <n>] [--palette <file.pal>] [--wav <file.wav>] [--no-audio] \
+                     [--headless <frames>] [--mute <channel,...>] [--solo <channel,...>] \
+                     [--volume <channel=volume,...>] [--stems <prefix>]
+Channels: pulse1, pulse2, triangle, noise, dmc, vrc6, vrc7, fds, mmc5, n163, s5b
+NSF tracks are switched with Page Up/Page Down, R presses the console's reset button";
+
+struct Options {
+    rom_path: String,
+    // 1 based like the players show it, only for NSF files
+    track: Option<u8>,
+    palette_path: Option<String>,
+    wav_path: Option<String>,
+    no_audio: bool,
+    // Number of frames to run without a window
+    headless_frames: Option<usize>,
+    channel_controls: ChannelControls,
+    // Every channel also goes to its own WAV file starting with this
+    stems_prefix: Option<String>,
+}
+
+fn parse_channel(name: &str) -> Channel {
+    Channel::from_name(name).expect(USAGE)
+}
+
+fn parse_args() -> Options {
+    let mut args = std::env::args().skip(1);
+    let mut rom_path = None;
+    let mut track = None;
+    let mut palette_path = None;
+    let mut wav_path = None;
+    let mut no_audio = false;
+    let mut headless_frames = None;
+    let mut channel_controls = ChannelControls::default();
+    let mut stems_prefix = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--track" => track = Some(args.next().and_then(|n| n.parse().ok()).expect(USAGE)),
+            "--palette" => palette_path = Some(args.next().expect(USAGE)),
+            "--wav" => wav_path = Some(args.next().expect(USAGE)),
+            "--no-audio" => no_audio = true,
+            "--headless" => {
+                headless_frames = Some(args.next().and_then(|n| n.parse().ok()).expect(USAGE))
+            }
+            "--mute" => {
+                for name in args.next().expect(USAGE).split(',') {
+                    channel_controls.set_muted(parse_channel(name), true);
+                }
+            }
+            "--solo" => {
+                for name in args.next().expect(USAGE).split(',') {
+                    channel_controls.set_soloed(parse_channel(name), true);
+                }
+            }
+            "--volume" => {
+                for setting in args.next().expect(USAGE).split(',') {
+                    let (name, volume) = setting.split_once('=').expect(USAGE);
+                    channel_controls.set_volume(parse_channel(name), volume.parse().expect(USAGE));
+                }
+            }
+            "--stems" => stems_prefix = Some(args.next().expect(USAGE)),
+            _ => rom_path = Some(arg),
+        }
+    }
+
+    Options {
+        rom_path: rom_path.expect(USAGE),
+        track,
+        palette_path,
+        wav_path,
+        no_audio,
+        headless_frames,
+        channel_controls,
+        stems_prefix,
+    }
+}
+
+// None if there's no way to play sound
+#[cfg(feature = "audio-device")]
+fn create_device_sink() -> Option<Box<dyn AudioSink>> {
+    match DeviceSink::new() {
+        Ok(sink) => Some(Box::new(sink)),
+        Err(err) => {
+            eprintln!("Couldn't open audio device, running without sound: {}", err);
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "audio-device"))]
+fn create_device_sink() -> Option<Box<dyn AudioSink>> {
+    None
+}
+
+// The sound card unless we're headless or muted, a WAV file gets written next to it at
+// whatever rate the sound card runs
+fn create_sink(options: &Options) -> Box<dyn AudioSink> {
+    let device = if options.no_audio || options.headless_frames.is_some() {
+        None
+    } else {
+        create_device_sink()
+    };
+    let sample_rate = device
+        .as_ref()
+        .map_or(DEFAULT_SAMPLE_RATE, |device| device.get_sample_rate());
+    let wav = options.wav_path.as_ref().map(|path| -> Box<dyn AudioSink> {
+        Box::new(WavSink::create(path, sample_rate).expect("Couldn't create WAV file"))
+    });
+
+    match (device, wav) {
+        (Some(device), Some(wav)) => Box::new(TeeSink::new(device, wav)),
+        (Some(sink), None) | (None, Some(sink)) => sink,
+        (None, None) => Box::new(NullSink::new(DEFAULT_SAMPLE_RATE)),
+    }
+}
 
 fn main() {
-    let (event_loop, window, mut pixels) = create_window(256, 240, "lol");
-    let mut scale = window.scale_factor();
-    let mut input = WinitInputHelper::new();
+    let options = parse_args();
+    let palette = match &options.palette_path {
+        Some(path) => SystemPalette::from_file(path).expect("Couldn't load palette"),
+        None => SystemPalette::default(),
+    };
+    let mut program = Program::load(&options);
+    let mut sink = create_sink(&options);
+    let bus = program.get_cpu_mut().get_bus_mut();
+    bus.set_sample_rate(sink.get_sample_rate());
+    *bus.get_channel_controls_mut() = options.channel_controls.clone();
 
-    let mut r = rand::thread_rng();
+    let mut stems = options.stems_prefix.as_ref().map(|prefix| {
+        StemWriter::create(prefix, sink.get_sample_rate()).expect("Couldn't create stem files")
+    });
+    program.get_cpu_mut().get_bus_mut().set_stems_enabled(stems.is_some());
 
-    for pixel in pixels.get_frame().chunks_exact_mut(4) {
-        pixel.copy_from_slice(&[r.gen(), r.gen(), r.gen(), 0xff])
+    if let Some(frames) = options.headless_frames {
+        for _ in 0..frames {
+            run_frame(&mut program, sink.as_mut(), stems.as_mut());
+        }
+        sink.finish();
+        if let Some(stems) = &mut stems {
+            stems.finish();
+        }
+        return;
     }
 
+    let (event_loop, window, mut pixels) =
+        create_window(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, "Nust");
+    let mut input = WinitInputHelper::new();
+
     event_loop.run(move |event, _, control_flow| {
         // Draw the current frame
         match event {
             Event::RedrawRequested(_) => {
+                run_frame(&mut program, sink.as_mut(), stems.as_mut());
+                draw_frame(pixels.get_frame(), program.get_cpu().get_bus().get_ppu().get_frame(), &palette);
+
                 if pixels.render().is_err() {
+                    sink.finish();
+                    if let Some(stems) = &mut stems {
+                        stems.finish();
+                    }
                     *control_flow = ControlFlow::Exit;
                     return;
                 }
@@ -38,10 +345,38 @@ fn main() {
 
         if input.update(&event) {
             if input.key_pressed(VirtualKeyCode::Escape) || input.quit() {
+                // The event loop never returns, so nothing gets dropped on the way out
+                sink.finish();
+                if let Some(stems) = &mut stems {
+                    stems.finish();
+                }
                 *control_flow = ControlFlow::Exit;
                 return;
             }
 
+            if let Program::Game(cpu) = &mut program {
+                if input.key_pressed(VirtualKeyCode::R) {
+                    cpu.reset();
+                }
+            }
+
+            if let Program::Music(player) = &mut program {
+                if input.key_pressed(VirtualKeyCode::PageDown) {
+                    player.next_song();
+                    print_song(player);
+                } else if input.key_pressed(VirtualKeyCode::PageUp) {
+                    player.previous_song();
+                    print_song(player);
+                }
+            }
+
+            let bus = program.get_cpu_mut().get_bus_mut();
+            update_channel_controls(&input, bus.get_channel_controls_mut());
+
+            for (player, keymap) in KEYMAPS.iter().enumerate() {
+                bus.set_buttons(player, read_buttons(&input, keymap));
+            }
+
             if let Some(size) = input.window_resized() {
                 pixels.resize_surface(size.width, size.height);
             }
@@ -30,13 +30,24 @@ bitflags! {
         const UNISYSTEM =   1;
         const PLAYCHOICE =  2;
         const NES2 =        8;
+        // Bits 2-3 are 0b10 on NES 2.0 headers, anything else is an older format
+        const NES2_ID =     0b1100;
     }
 }
 
+impl InesFlags7 {
+    pub fn is_nes2(&self) -> bool {
+        *self & InesFlags7::NES2_ID == InesFlags7::NES2
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Mirroring {
     Vertical,
     Horizontal,
     FourWay,
+    SingleScreenA,
+    SingleScreenB,
 }
 
 #[derive(Debug)]
@@ -54,6 +65,10 @@ pub struct InesHeader {
     // In 8Kib units
     pub flags: InesHeaderFlags,
     pub mapper: u8,
+    // Only NES 2.0 headers have one, 0 otherwise
+    pub submapper: u8,
+    // In 8Kib units, old dumps leave it at 0 which also means 8Kib
+    pub prg_ram_size: usize,
 }
 
 #[derive(Debug)]
@@ -71,6 +86,52 @@ pub struct Cartridge {
     pub chr_rom: Vec<u8>,
     pub mapper: Box<dyn Mapper>,
     pub mirroring: Mirroring,
+    // The other 2 nametables of four screen boards live on the cartridge
+    pub extra_vram: Vec<u8>,
+    pub prg_ram: Vec<u8>,
+}
+
+impl Cartridge {
+    // Mappers with mirroring control override the hardwired mirroring
+    pub fn get_mirroring(&self) -> Mirroring {
+        self.mapper.mirroring().unwrap_or(self.mirroring)
+    }
+
+    // Everything from $4020 up, None when nothing on the cartridge answers
+    pub fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        if let Some(value) = self.mapper.cpu_read_register(addr) {
+            return Some(value);
+        }
+        if let Some(mapped) = self.mapper.prg_ram_map_read(addr) {
+            return self.prg_ram.get(mapped).copied();
+        }
+        self.mapper
+            .cpu_map_read(addr)
+            .map(|mapped| self.prg_rom[mapped])
+    }
+
+    pub fn cpu_write(&mut self, addr: u16, value: u8) {
+        // ROM isn't writable, but the mapper's registers might be behind it
+        self.mapper.cpu_map_write(addr, value);
+        if let Some(mapped) = self.mapper.prg_ram_map_write(addr) {
+            if let Some(byte) = self.prg_ram.get_mut(mapped) {
+                *byte = value;
+            }
+        }
+    }
+
+    pub fn ppu_read(&mut self, addr: u16) -> u8 {
+        match self.mapper.ppu_map_read(addr) {
+            Some(mapped) => self.chr_rom[mapped],
+            None => 0,
+        }
+    }
+
+    pub fn ppu_write(&mut self, addr: u16, value: u8) {
+        if let Some(mapped) = self.mapper.ppu_map_write(addr, value) {
+            self.chr_rom[mapped] = value;
+        }
+    }
 }
 
 fn sign_parse(input: &[u8]) -> IResult<&[u8], &[u8]> {
@@ -97,7 +158,19 @@ fn parse_ines_header(input: &[u8]) -> IResult<&[u8], InesHeader> {
         tuple((sign_parse, be_u8, be_u8, mapper_flags_parse, take(8usize))),
     )(input)
     .map(|(next_input, res)| {
-        let (_signature, prg_size, chr_size, (mapper, flags6, flags7), _) = res;
+        let (_signature, prg_size, chr_size, (mapper, flags6, flags7), rest) = res;
+        // NES 2.0 headers keep the submapper in byte 8 and move the PRG RAM size to byte 10,
+        // as shift counts for the volatile and battery backed parts
+        let (submapper, prg_ram_size) = if flags7.is_nes2() {
+            let prg_ram_bytes: usize = [rest[2] & 0x0F, rest[2] >> 4]
+                .iter()
+                .filter(|&&shift| shift != 0)
+                .map(|&shift| 64 << shift)
+                .sum();
+            (rest[0] >> 4, prg_ram_bytes.div_ceil(0x2000))
+        } else {
+            (0, rest[0] as usize)
+        };
         (
             next_input,
             InesHeader {
@@ -105,6 +178,8 @@ fn parse_ines_header(input: &[u8]) -> IResult<&[u8], InesHeader> {
                 chr_size,
                 flags: InesHeaderFlags { flags6, flags7 },
                 mapper,
+                submapper,
+                prg_ram_size,
             },
         )
     })
@@ -154,23 +229,33 @@ fn get_file_as_byte_vec(filename: &str) -> Vec<u8> {
 
 fn ines_to_cartridge(ines: InesFile) -> Cartridge {
     let mapper = get_mapper(&ines).unwrap();
+    let mirroring = if ines.header.flags.flags6.contains(InesFlags6::FOUR_SCREEN) {
+        Mirroring::FourWay
+    } else {
+        if ines.header.flags.flags6.contains(InesFlags6::MIRRORING) {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        }
+    };
 
     Cartridge {
         trainer: ines.trainer.to_owned(),
         prg_rom: ines.prg_rom.to_owned(),
-        chr_rom: ines.chr_rom.to_owned(),
+        // No CHR ROM means the board has 8Kib of CHR RAM instead
+        chr_rom: if ines.header.chr_size == 0 {
+            vec![0; 0x2000]
+        } else {
+            ines.chr_rom.to_owned()
+        },
         mapper,
-        mirroring: {
-            if ines.header.flags.flags6.contains(InesFlags6::FOUR_SCREEN) {
-                Mirroring::FourWay
-            } else {
-                if ines.header.flags.flags6.contains(InesFlags6::MIRRORING) {
-                    Mirroring::Vertical
-                } else {
-                    Mirroring::Horizontal
-                }
-            }
+        mirroring,
+        extra_vram: if mirroring == Mirroring::FourWay {
+            vec![0; 0x800]
+        } else {
+            vec![]
         },
+        prg_ram: vec![0; ines.header.prg_ram_size.max(1) * 0x2000],
     }
 }
 
@@ -0,0 +1,415 @@
+use crate::bus::mappers::get_nsf_mapper;
+use crate::nes_parser::{Cartridge, Mirroring};
+use bitflags::bitflags;
+use nom::{
+    bytes::complete::{tag, take},
+    combinator::{opt, rest},
+    error::{context, Error, ErrorKind},
+    multi::many0,
+    number::complete::{be_u8, le_u16, le_u32},
+    sequence::tuple,
+    IResult,
+};
+use std::convert::TryInto;
+use std::fs;
+
+// NSF files are a dump of a game's music engine together with its data, we run them by
+// calling INIT once per song and PLAY at a fixed rate. NSFe has the same information
+// split into chunks, with some extra metadata like track names
+
+const NSF_SIGNATURE: &[u8] = b"NESM\x1A";
+const NSFE_SIGNATURE: &[u8] = b"NSFE";
+
+// Microseconds between PLAY calls when the file doesn't say, 60.1Hz
+pub const DEFAULT_PLAY_SPEED: u16 = 16639;
+// Same on PAL, 50Hz
+pub const DEFAULT_PAL_PLAY_SPEED: u16 = 19997;
+
+bitflags! {
+    #[derive(Default)]
+    pub struct NsfExpansion: u8 {
+        const VRC6 =    1;
+        const VRC7 =    2;
+        const FDS =     4;
+        const MMC5 =    8;
+        const N163 =    16;
+        const S5B =     32;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NsfFile {
+    pub total_songs: u8,
+    // Zero based, unlike in the NSF header
+    pub starting_song: u8,
+    pub load_addr: u16,
+    pub init_addr: u16,
+    pub play_addr: u16,
+    pub name: String,
+    pub artist: String,
+    pub copyright: String,
+    // Microseconds between PLAY calls on NTSC
+    pub play_speed: u16,
+    pub pal_play_speed: u16,
+    // Bit 0 is set for PAL tunes, bit 1 for tunes that play on both
+    pub region: u8,
+    // Initial 4Kib banks for $8000-$FFFF, None if the tune isn't bankswitched
+    pub bankswitch_init: Option<[u8; 8]>,
+    pub expansion: NsfExpansion,
+    // Only NSFe files have these
+    pub track_labels: Vec<String>,
+    pub data: Vec<u8>,
+}
+
+impl NsfFile {
+    pub fn get_track_label(&self, song: u8) -> Option<&str> {
+        self.track_labels.get(song as usize).map(|label| label.as_str())
+    }
+
+    // Tunes that play on both get the NTSC treatment
+    pub fn is_pal_only(&self) -> bool {
+        self.region & 3 == 1
+    }
+
+    pub fn get_play_speed(&self) -> u16 {
+        if self.is_pal_only() {
+            self.pal_play_speed
+        } else {
+            self.play_speed
+        }
+    }
+}
+
+// Strings in both formats are null terminated, NSF pads them to 32 bytes
+fn parse_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn parse_strings(bytes: &[u8]) -> Vec<String> {
+    let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+    bytes.split(|&byte| byte == 0).map(parse_string).collect()
+}
+
+// All zeros means no bankswitching
+fn parse_bankswitch_init(bytes: &[u8]) -> Option<[u8; 8]> {
+    if bytes.iter().any(|&bank| bank != 0) {
+        bytes.try_into().ok()
+    } else {
+        None
+    }
+}
+
+fn parse_nsf_bytes(input: &[u8]) -> IResult<&[u8], NsfFile> {
+    let (input, header) = context(
+        "NSF header parser",
+        tuple((
+            tag(NSF_SIGNATURE),
+            be_u8,         // Version
+            be_u8,         // Total songs
+            be_u8,         // Starting song
+            le_u16,        // Load address
+            le_u16,        // Init address
+            le_u16,        // Play address
+            take(32usize), // Name
+            take(32usize), // Artist
+            take(32usize), // Copyright
+            le_u16,        // NTSC play speed
+            take(8usize),  // Bankswitch init
+            le_u16,        // PAL play speed
+            be_u8,         // PAL/NTSC
+            be_u8,         // Expansion audio
+            be_u8,         // NSF2 flags
+            take(3usize),  // NSF2 program data length
+        )),
+    )(input)?;
+    let (
+        _signature,
+        _version,
+        total_songs,
+        starting_song,
+        load_addr,
+        init_addr,
+        play_addr,
+        name,
+        artist,
+        copyright,
+        play_speed,
+        bankswitch_init,
+        pal_play_speed,
+        region,
+        expansion,
+        _nsf2_flags,
+        data_length,
+    ) = header;
+
+    // NSF2 files can have metadata after the program data, 0 means it runs to the end
+    let data_length = u32::from_le_bytes([data_length[0], data_length[1], data_length[2], 0]);
+    let (input, data) = if data_length == 0 {
+        rest(input)?
+    } else {
+        take(data_length as usize)(input)?
+    };
+
+    check_load_addr(
+        input,
+        NsfFile {
+            total_songs,
+            starting_song: starting_song.saturating_sub(1),
+            load_addr,
+            init_addr,
+            play_addr,
+            name: parse_string(name),
+            artist: parse_string(artist),
+            copyright: parse_string(copyright),
+            play_speed: if play_speed == 0 { DEFAULT_PLAY_SPEED } else { play_speed },
+            pal_play_speed: if pal_play_speed == 0 {
+                DEFAULT_PAL_PLAY_SPEED
+            } else {
+                pal_play_speed
+            },
+            region,
+            bankswitch_init: parse_bankswitch_init(bankswitch_init),
+            expansion: NsfExpansion::from_bits_truncate(expansion),
+            track_labels: vec![],
+            data: data.to_vec(),
+        },
+    )
+}
+
+fn parse_nsfe_chunk(input: &[u8]) -> IResult<&[u8], (&[u8], &[u8])> {
+    let (input, (length, id)) = tuple((le_u32, take(4usize)))(input)?;
+    let (input, data) = take(length as usize)(input)?;
+    Ok((input, (id, data)))
+}
+
+struct NsfeInfo {
+    load_addr: u16,
+    init_addr: u16,
+    play_addr: u16,
+    region: u8,
+    expansion: u8,
+    total_songs: Option<u8>,
+    starting_song: Option<u8>,
+}
+
+fn parse_nsfe_info(input: &[u8]) -> IResult<&[u8], NsfeInfo> {
+    let (input, (load_addr, init_addr, play_addr, region, expansion, total_songs, starting_song)) =
+        context(
+            "NSFe INFO chunk",
+            tuple((le_u16, le_u16, le_u16, be_u8, be_u8, opt(be_u8), opt(be_u8))),
+        )(input)?;
+    Ok((
+        input,
+        NsfeInfo {
+            load_addr,
+            init_addr,
+            play_addr,
+            region,
+            expansion,
+            total_songs,
+            starting_song,
+        },
+    ))
+}
+
+// Tunes without bankswitching get copied in flat from their load address, which has to be
+// somewhere in ROM, or in RAM from $6000 for FDS tunes
+fn check_load_addr(input: &[u8], nsf: NsfFile) -> IResult<&[u8], NsfFile> {
+    let lowest = if nsf.expansion.contains(NsfExpansion::FDS) {
+        0x6000
+    } else {
+        0x8000
+    };
+    if nsf.bankswitch_init.is_none() && nsf.load_addr < lowest {
+        Err(nom::Err::Failure(Error::new(input, ErrorKind::Verify)))
+    } else {
+        Ok((input, nsf))
+    }
+}
+
+fn parse_nsfe_bytes(input: &[u8]) -> IResult<&[u8], NsfFile> {
+    let (input, (_signature, chunks)) =
+        context("NSFe file parser", tuple((tag(NSFE_SIGNATURE), many0(parse_nsfe_chunk))))(input)?;
+
+    let mut nsf = NsfFile {
+        total_songs: 1,
+        starting_song: 0,
+        load_addr: 0,
+        init_addr: 0,
+        play_addr: 0,
+        name: String::new(),
+        artist: String::new(),
+        copyright: String::new(),
+        play_speed: DEFAULT_PLAY_SPEED,
+        pal_play_speed: DEFAULT_PAL_PLAY_SPEED,
+        region: 0,
+        bankswitch_init: None,
+        expansion: NsfExpansion::empty(),
+        track_labels: vec![],
+        data: vec![],
+    };
+
+    for (id, data) in chunks {
+        match id {
+            b"INFO" => {
+                let (_, info) = parse_nsfe_info(data)?;
+                nsf.load_addr = info.load_addr;
+                nsf.init_addr = info.init_addr;
+                nsf.play_addr = info.play_addr;
+                nsf.region = info.region;
+                nsf.expansion = NsfExpansion::from_bits_truncate(info.expansion);
+                nsf.total_songs = info.total_songs.unwrap_or(1);
+                nsf.starting_song = info.starting_song.unwrap_or(0);
+            }
+            b"DATA" => nsf.data = data.to_vec(),
+            b"BANK" => {
+                let mut banks = [0; 8];
+                banks[..data.len().min(8)].copy_from_slice(&data[..data.len().min(8)]);
+                nsf.bankswitch_init = parse_bankswitch_init(&banks);
+            }
+            b"RATE" => {
+                let (data, play_speed) = le_u16(data)?;
+                let (_, pal_play_speed) = opt(le_u16)(data)?;
+                if play_speed != 0 {
+                    nsf.play_speed = play_speed;
+                }
+                if let Some(pal_play_speed) = pal_play_speed.filter(|&speed| speed != 0) {
+                    nsf.pal_play_speed = pal_play_speed;
+                }
+            }
+            b"auth" => {
+                let mut strings = parse_strings(data).into_iter();
+                nsf.name = strings.next().unwrap_or_default();
+                nsf.artist = strings.next().unwrap_or_default();
+                nsf.copyright = strings.next().unwrap_or_default();
+            }
+            b"tlbl" => nsf.track_labels = parse_strings(data),
+            b"NEND" => break,
+            // Chunks starting with an uppercase letter have to be understood to play the file
+            _ if id[0].is_ascii_uppercase() => {
+                return Err(nom::Err::Failure(Error::new(id, ErrorKind::Tag)))
+            }
+            _ => (),
+        }
+    }
+
+    check_load_addr(input, nsf)
+}
+
+pub fn is_nsf_file(filename: &str) -> bool {
+    match fs::read(filename) {
+        Ok(contents) => {
+            contents.starts_with(NSF_SIGNATURE) || contents.starts_with(NSFE_SIGNATURE)
+        }
+        Err(_) => false,
+    }
+}
+
+pub fn get_nsf_from_file(filename: &str) -> NsfFile {
+    let contents = fs::read(filename).expect("no file found");
+    let result = if contents.starts_with(NSFE_SIGNATURE) {
+        parse_nsfe_bytes(&contents)
+    } else {
+        parse_nsf_bytes(&contents)
+    };
+
+    match result {
+        Ok((_, nsf)) => nsf,
+        // An unsupported NSFe chunk fails on its ID, a bad load address at the end of the file
+        Err(nom::Err::Error(err)) | Err(nom::Err::Failure(err)) => panic!(
+            "Couldn't parse NSF file: {:?} at byte {:#X}",
+            err.code,
+            contents.len() - err.input.len()
+        ),
+        Err(nom::Err::Incomplete(_)) => panic!("Couldn't parse NSF file: it ends too early"),
+    }
+}
+
+// Lays the data out in 4Kib banks, tunes without bankswitching get a flat 32Kib image, or
+// 40Kib from $6000 for FDS tunes. Bankswitched FDS tunes start $6000-$7FFF with the banks
+// of $E000-$FFFF
+pub fn nsf_to_cartridge(nsf: &NsfFile) -> Cartridge {
+    let fds = nsf.expansion.contains(NsfExpansion::FDS);
+    // The parser made sure the load address is in ROM, or RAM for FDS tunes
+    let (padding, banks, fds_banks) = match nsf.bankswitch_init {
+        Some(banks) => ((nsf.load_addr & 0xFFF) as usize, banks, [banks[6], banks[7]]),
+        None if fds => ((nsf.load_addr - 0x6000) as usize, [2, 3, 4, 5, 6, 7, 8, 9], [0, 1]),
+        None => ((nsf.load_addr - 0x8000) as usize, [0, 1, 2, 3, 4, 5, 6, 7], [0, 0]),
+    };
+
+    let mut prg_rom = vec![0; padding];
+    prg_rom.extend_from_slice(&nsf.data);
+    let size = match nsf.bankswitch_init {
+        Some(_) => (prg_rom.len() + 0xFFF) & !0xFFF,
+        None if fds => 0xA000,
+        None => 0x8000,
+    };
+    prg_rom.resize(size, 0);
+
+    Cartridge {
+        trainer: None,
+        mapper: get_nsf_mapper(
+            banks,
+            nsf.bankswitch_init.is_some(),
+            &prg_rom,
+            fds_banks,
+            nsf.expansion,
+        ),
+        prg_rom,
+        // The PPU isn't used, but give it some CHR RAM to look at
+        chr_rom: vec![0; 0x2000],
+        mirroring: Mirroring::Horizontal,
+        extra_vram: vec![],
+        prg_ram: vec![0; 0x2000],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(load_addr: u16, expansion: u8) -> Vec<u8> {
+        let mut header = vec![0; 0x80];
+        header[..5].copy_from_slice(NSF_SIGNATURE);
+        header[5] = 1; // Version
+        header[6] = 3; // Total songs
+        header[7] = 2; // Starting song
+        header[0x08..0x0A].copy_from_slice(&load_addr.to_le_bytes());
+        header[0x0A..0x0C].copy_from_slice(&0x8003u16.to_le_bytes());
+        header[0x0C..0x0E].copy_from_slice(&0x8006u16.to_le_bytes());
+        header[0x0E..0x0E + 4].copy_from_slice(b"Song");
+        header[0x2E..0x2E + 6].copy_from_slice(b"Artist");
+        header[0x7A] = 1; // PAL
+        header[0x7B] = expansion;
+        header
+    }
+
+    #[test]
+    fn parses_the_header() {
+        let mut bytes = header(0x8000, 0x21);
+        bytes.extend_from_slice(&[0xEA; 16]);
+        let (_, nsf) = parse_nsf_bytes(&bytes).unwrap();
+
+        assert_eq!(nsf.total_songs, 3);
+        assert_eq!(nsf.starting_song, 1);
+        assert_eq!(nsf.load_addr, 0x8000);
+        assert_eq!(nsf.init_addr, 0x8003);
+        assert_eq!(nsf.play_addr, 0x8006);
+        assert_eq!(nsf.name, "Song");
+        assert_eq!(nsf.artist, "Artist");
+        assert_eq!(nsf.copyright, "");
+        assert!(nsf.is_pal_only());
+        assert_eq!(nsf.get_play_speed(), DEFAULT_PAL_PLAY_SPEED);
+        assert_eq!(nsf.play_speed, DEFAULT_PLAY_SPEED);
+        assert!(nsf.bankswitch_init.is_none());
+        assert_eq!(nsf.expansion, NsfExpansion::VRC6 | NsfExpansion::S5B);
+        assert_eq!(nsf.data, vec![0xEA; 16]);
+    }
+
+    #[test]
+    fn only_fds_tunes_load_below_8000() {
+        assert!(parse_nsf_bytes(&header(0x6000, 0)).is_err());
+        assert!(parse_nsf_bytes(&header(0x6000, NsfExpansion::FDS.bits())).is_ok());
+    }
+}
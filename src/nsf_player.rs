@@ -0,0 +1,132 @@
+use crate::apu::CPU_FREQUENCY;
+use crate::bus::Bus;
+use crate::cpu::{Cpu, CpuFlags};
+use crate::nsf_parser::{nsf_to_cartridge, NsfFile};
+
+// INIT and PLAY return here, nothing is mapped at it so no real code ever runs there
+const RETURN_ADDR: u16 = 0x5FF0;
+
+// Runs an NSF on a fresh console per song, the PPU keeps ticking so frames pace like a game
+pub struct NsfPlayer {
+    nsf: NsfFile,
+    song: u8,
+    cpu: Cpu,
+    // In CPU cycles
+    play_period: usize,
+    next_play: usize,
+    // Whether INIT or PLAY is still running
+    in_routine: bool,
+}
+
+impl NsfPlayer {
+    pub fn new(nsf: NsfFile) -> Self {
+        let song = nsf.starting_song.min(nsf.total_songs.saturating_sub(1));
+        let play_period = (nsf.get_play_speed() as f64 * CPU_FREQUENCY / 1_000_000.0) as usize;
+        let cpu = Cpu::create_from_bus(Bus::create_from_crt(nsf_to_cartridge(&nsf)));
+
+        let mut player = NsfPlayer {
+            nsf,
+            song,
+            cpu,
+            play_period,
+            next_play: 0,
+            in_routine: false,
+        };
+        player.init_song();
+        player
+    }
+
+    // Songs start from a clean machine, only the audio settings are carried over
+    fn init_song(&mut self) {
+        let old_bus = self.cpu.get_bus();
+        let sample_rate = old_bus.get_sample_rate();
+        let channel_controls = old_bus.get_channel_controls().clone();
+        let stems_enabled = old_bus.get_stems_enabled();
+
+        let mut bus = Bus::create_from_crt(nsf_to_cartridge(&self.nsf));
+        bus.set_sample_rate(sample_rate);
+        *bus.get_channel_controls_mut() = channel_controls;
+        bus.set_stems_enabled(stems_enabled);
+
+        // The APU state INIT expects
+        for addr in 0x4000..=0x4013 {
+            bus.cpu_write(addr, 0);
+        }
+        bus.cpu_write(0x4015, 0x0F);
+        bus.cpu_write(0x4017, 0x40);
+
+        self.cpu = Cpu::create_from_bus(bus);
+        self.cpu.status.insert(CpuFlags::I);
+        self.cpu.stack_pointer = 0xFF;
+        self.cpu.reg_a = self.song;
+        // The console is always NTSC, but PAL only tunes are told they're on PAL and get
+        // called at the PAL rate so they at least keep their tempo
+        self.cpu.reg_x = self.nsf.is_pal_only() as u8;
+        self.call(self.nsf.init_addr);
+        self.next_play = self.cpu.get_bus().get_cycles();
+    }
+
+    // JSR to the routine, its RTS lands on RETURN_ADDR
+    fn call(&mut self, addr: u16) {
+        self.cpu.stack_push_word(RETURN_ADDR - 1);
+        self.cpu.program_counter = addr;
+        self.in_routine = true;
+    }
+
+    fn step(&mut self) {
+        if self.in_routine {
+            if self.cpu.program_counter == RETURN_ADDR {
+                self.in_routine = false;
+            } else {
+                self.cpu.execute_next();
+            }
+        } else if self.cpu.get_bus().get_cycles() >= self.next_play {
+            // A PLAY running late pushes back the ones after it instead of bunching up
+            self.next_play = self.cpu.get_bus().get_cycles() + self.play_period;
+            self.call(self.nsf.play_addr);
+        } else {
+            // The CPU idles between calls
+            self.cpu.get_bus_mut().cycle(1);
+        }
+    }
+
+    pub fn run_frame(&mut self) {
+        let frame = self.cpu.get_bus().get_ppu().get_frame_count();
+        while self.cpu.get_bus().get_ppu().get_frame_count() == frame {
+            self.step();
+        }
+    }
+
+    pub fn set_song(&mut self, song: u8) {
+        self.song = song % self.nsf.total_songs.max(1);
+        self.init_song();
+    }
+
+    pub fn next_song(&mut self) {
+        self.set_song(self.song.wrapping_add(1));
+    }
+
+    pub fn previous_song(&mut self) {
+        self.set_song(if self.song == 0 {
+            self.nsf.total_songs.saturating_sub(1)
+        } else {
+            self.song - 1
+        });
+    }
+
+    pub fn get_song(&self) -> u8 {
+        self.song
+    }
+
+    pub fn get_nsf(&self) -> &NsfFile {
+        &self.nsf
+    }
+
+    pub fn get_cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    pub fn get_cpu_mut(&mut self) -> &mut Cpu {
+        &mut self.cpu
+    }
+}
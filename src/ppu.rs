@@ -1,3 +1,327 @@
-pub(crate) struct Ppu {
-
-}
\ No newline at end of file
+use bitflags::bitflags;
+
+use crate::nes_parser::{Cartridge, Mirroring};
+use sprites::{SpriteUnit, MAX_SPRITES_PER_LINE};
+
+mod background;
+pub mod palette;
+pub mod registers;
+mod scroll;
+pub mod sprites;
+
+pub const SCREEN_WIDTH: usize = 256;
+pub const SCREEN_HEIGHT: usize = 240;
+
+pub const DOTS_PER_SCANLINE: u16 = 341;
+pub const SCANLINES_PER_FRAME: u16 = 262;
+
+const VBLANK_SCANLINE: u16 = 241;
+const PRE_RENDER_SCANLINE: u16 = 261;
+
+bitflags! {
+    #[derive(Default)]
+    pub struct PpuCtrl: u8 {
+        const NAMETABLE_X =         0b00000001;
+        const NAMETABLE_Y =         0b00000010;
+        const VRAM_INCREMENT =      0b00000100; // 0 - add 1 going across, 1 - add 32 going down
+        const SPRITE_PATTERN =      0b00001000;
+        const BACKGROUND_PATTERN =  0b00010000;
+        const SPRITE_SIZE =         0b00100000; // 0 - 8x8, 1 - 8x16
+        const MASTER_SLAVE =        0b01000000; // Unused on the NES, grounding EXT is not our problem
+        const NMI_ENABLE =          0b10000000;
+    }
+}
+
+bitflags! {
+    #[derive(Default)]
+    pub struct PpuMask: u8 {
+        const GREYSCALE =           0b00000001;
+        const SHOW_BACKGROUND_LEFT = 0b00000010;
+        const SHOW_SPRITES_LEFT =   0b00000100;
+        const SHOW_BACKGROUND =     0b00001000;
+        const SHOW_SPRITES =        0b00010000;
+        const EMPHASIZE_RED =       0b00100000;
+        const EMPHASIZE_GREEN =     0b01000000;
+        const EMPHASIZE_BLUE =      0b10000000;
+    }
+}
+
+bitflags! {
+    #[derive(Default)]
+    pub struct PpuStatus: u8 {
+        const SPRITE_OVERFLOW =     0b00100000;
+        const SPRITE_ZERO_HIT =     0b01000000;
+        const VBLANK =              0b10000000;
+    }
+}
+
+pub struct Ppu {
+    ctrl: PpuCtrl,
+    mask: PpuMask,
+    status: PpuStatus,
+
+    oam_addr: u8,
+    oam: [u8; 0x100],
+
+    vram: [u8; 0x800],
+    palette: [u8; 0x20],
+
+    // Loopy's internal registers, see scroll.rs
+    v: u16,
+    t: u16,
+    fine_x: u8,
+    write_toggle: bool,
+
+    // PPUDATA reads are delayed by one read through this buffer
+    read_buffer: u8,
+    // Last value driven on the CPU-PPU data bus, what write-only registers read back as
+    open_bus: u8,
+
+    // Background pipeline state, see background.rs
+    bg_next_tile_id: u8,
+    bg_next_tile_attrib: u8,
+    bg_next_tile_lsb: u8,
+    bg_next_tile_msb: u8,
+    bg_shifter_pattern_lo: u16,
+    bg_shifter_pattern_hi: u16,
+    bg_shifter_attrib_lo: u8,
+    bg_shifter_attrib_hi: u8,
+    bg_attrib_latch_lo: bool,
+    bg_attrib_latch_hi: bool,
+
+    // Sprite pipeline state, see sprites.rs
+    secondary_oam: [u8; 32],
+    sprite_count: usize,
+    sprite_zero_next: bool,
+    sprite_units: [SpriteUnit; MAX_SPRITES_PER_LINE],
+    sprite_line_count: usize,
+    sprite_zero_on_line: bool,
+
+    // Colour indices of the last drawn frame with the emphasis bits on top (eeecccccc)
+    frame: Vec<u16>,
+
+    dot: u16,
+    scanline: u16,
+    frame_count: usize,
+}
+
+impl Ppu {
+    pub fn new() -> Self {
+        Ppu {
+            ctrl: PpuCtrl::empty(),
+            mask: PpuMask::empty(),
+            status: PpuStatus::empty(),
+            oam_addr: 0,
+            oam: [0; 0x100],
+            vram: [0; 0x800],
+            palette: [0; 0x20],
+            v: 0,
+            t: 0,
+            fine_x: 0,
+            write_toggle: false,
+            read_buffer: 0,
+            open_bus: 0,
+            bg_next_tile_id: 0,
+            bg_next_tile_attrib: 0,
+            bg_next_tile_lsb: 0,
+            bg_next_tile_msb: 0,
+            bg_shifter_pattern_lo: 0,
+            bg_shifter_pattern_hi: 0,
+            bg_shifter_attrib_lo: 0,
+            bg_shifter_attrib_hi: 0,
+            bg_attrib_latch_lo: false,
+            bg_attrib_latch_hi: false,
+            secondary_oam: [0xFF; 32],
+            sprite_count: 0,
+            sprite_zero_next: false,
+            sprite_units: [SpriteUnit::default(); MAX_SPRITES_PER_LINE],
+            sprite_line_count: 0,
+            sprite_zero_on_line: false,
+            frame: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            dot: 0,
+            scanline: 0,
+            frame_count: 0,
+        }
+    }
+
+    // The reset button clears PPUCTRL, PPUMASK and the scroll, memory and OAM survive it
+    pub fn reset(&mut self) {
+        self.ctrl = PpuCtrl::empty();
+        self.mask = PpuMask::empty();
+        self.t = 0;
+        self.fine_x = 0;
+        self.write_toggle = false;
+        self.read_buffer = 0;
+    }
+
+    // Which of the 4 logical nametables in $2000-$2FFF ends up in which physical one,
+    // 0 and 1 are the console's VRAM, 2 and 3 only exist on four screen cartridges
+    fn nametable_index(addr: u16, mirroring: Mirroring) -> (usize, usize) {
+        let table = (addr >> 10) & 3;
+        let physical = match mirroring {
+            Mirroring::Vertical => table & 1,
+            Mirroring::Horizontal => table >> 1,
+            Mirroring::FourWay => table,
+            Mirroring::SingleScreenA => 0,
+            Mirroring::SingleScreenB => 1,
+        };
+        (physical as usize, (addr & 0x3FF) as usize)
+    }
+
+    fn nametable_read(&self, addr: u16, crt: &Cartridge) -> u8 {
+        match Ppu::nametable_index(addr, crt.get_mirroring()) {
+            (table @ 0..=1, offset) => self.vram[table * 0x400 + offset],
+            (table, offset) => crt.extra_vram[(table - 2) * 0x400 + offset],
+        }
+    }
+
+    fn nametable_write(&mut self, addr: u16, value: u8, crt: &mut Cartridge) {
+        match Ppu::nametable_index(addr, crt.get_mirroring()) {
+            (table @ 0..=1, offset) => self.vram[table * 0x400 + offset] = value,
+            (table, offset) => crt.extra_vram[(table - 2) * 0x400 + offset] = value,
+        }
+    }
+
+    // Palette RAM is 32 bytes mirrored across $3F00-$3FFF, and the sprite palettes'
+    // transparent entries ($3F10/$3F14/$3F18/$3F1C) are the background ones
+    fn palette_index(addr: u16) -> usize {
+        let index = (addr & 0x1F) as usize;
+        if index & 0x13 == 0x10 {
+            index & 0x0F
+        } else {
+            index
+        }
+    }
+
+    // The PPU's own address space, $0000-$3FFF
+    pub fn ppu_read(&self, addr: u16, crt: &mut Cartridge) -> u8 {
+        let addr = addr & 0x3FFF;
+        match addr {
+            0x0000..=0x1FFF => crt.ppu_read(addr),
+            // $3000-$3EFF mirrors $2000-$2EFF
+            0x2000..=0x3EFF => self.nametable_read(addr & 0x2FFF, crt),
+            0x3F00..=0x3FFF => self.palette[Ppu::palette_index(addr)],
+            _ => unreachable!("PPU reading match failed - impossible!"),
+        }
+    }
+
+    pub fn ppu_write(&mut self, addr: u16, value: u8, crt: &mut Cartridge) {
+        let addr = addr & 0x3FFF;
+        match addr {
+            0x0000..=0x1FFF => crt.ppu_write(addr, value),
+            0x2000..=0x3EFF => self.nametable_write(addr & 0x2FFF, value, crt),
+            0x3F00..=0x3FFF => self.palette[Ppu::palette_index(addr)] = value,
+            _ => unreachable!("PPU writing match failed - impossible!"),
+        }
+    }
+
+    fn rendering_enabled(&self) -> bool {
+        self.mask
+            .intersects(PpuMask::SHOW_BACKGROUND | PpuMask::SHOW_SPRITES)
+    }
+
+    // Rendering is in progress and owns v
+    fn is_rendering(&self) -> bool {
+        self.rendering_enabled()
+            && (self.scanline < SCREEN_HEIGHT as u16 || self.scanline == PRE_RENDER_SCANLINE)
+    }
+
+    fn render_pixel(&mut self, crt: &mut Cartridge) {
+        let (mut bg_pixel, bg_palette) = self.bg_pixel();
+        let mut sprite = self.sprite_pixel();
+
+        // The leftmost 8 pixels can be hidden separately, handy for hiding scroll seams
+        if self.dot <= 8 {
+            if !self.mask.contains(PpuMask::SHOW_BACKGROUND_LEFT) {
+                bg_pixel = 0;
+            }
+            if !self.mask.contains(PpuMask::SHOW_SPRITES_LEFT) {
+                sprite = None;
+            }
+        }
+
+        let (pixel, palette) = match sprite {
+            Some(sprite) => {
+                // Sprite 0 hit never happens on the last dot
+                if sprite.sprite_zero && bg_pixel != 0 && self.dot != 256 {
+                    self.status.insert(PpuStatus::SPRITE_ZERO_HIT);
+                }
+
+                if bg_pixel != 0 && sprite.behind_background {
+                    (bg_pixel, bg_palette)
+                } else {
+                    (sprite.pixel, sprite.palette)
+                }
+            }
+            None => (bg_pixel, bg_palette),
+        };
+
+        let colour_addr = if pixel == 0 {
+            0x3F00 // Universal background colour
+        } else {
+            0x3F00 + ((palette as u16) << 2) + pixel as u16
+        };
+
+        let x = (self.dot - 1) as usize;
+        let y = self.scanline as usize;
+        let mut colour = self.ppu_read(colour_addr, crt) & 0x3F;
+        if self.mask.contains(PpuMask::GREYSCALE) {
+            colour &= 0x30;
+        }
+
+        let emphasis = (self.mask.bits() >> 5) as u16;
+        self.frame[y * SCREEN_WIDTH + x] = (emphasis << 6) | colour as u16;
+    }
+
+    pub fn clock(&mut self, crt: &mut Cartridge) {
+        let visible_line = self.scanline < SCREEN_HEIGHT as u16;
+
+        if self.is_rendering() {
+            self.bg_clock(crt);
+            self.sprite_clock(crt);
+        }
+
+        if visible_line && (1..=SCREEN_WIDTH as u16).contains(&self.dot) {
+            self.render_pixel(crt);
+        }
+
+        if self.scanline == VBLANK_SCANLINE && self.dot == 1 {
+            self.status.insert(PpuStatus::VBLANK);
+        }
+
+        if self.scanline == PRE_RENDER_SCANLINE && self.dot == 1 {
+            self.status.remove(PpuStatus::all());
+        }
+
+        // Odd frames skip the last dot of the pre-render line when rendering
+        if self.scanline == PRE_RENDER_SCANLINE
+            && self.dot == 339
+            && self.frame_count % 2 == 1
+            && self.rendering_enabled()
+        {
+            self.dot += 1;
+        }
+
+        self.dot += 1;
+        if self.dot == DOTS_PER_SCANLINE {
+            self.dot = 0;
+            self.scanline += 1;
+            if self.scanline == SCANLINES_PER_FRAME {
+                self.scanline = 0;
+                self.frame_count += 1;
+            }
+        }
+    }
+
+    pub fn nmi_line(&self) -> bool {
+        self.status.contains(PpuStatus::VBLANK) && self.ctrl.contains(PpuCtrl::NMI_ENABLE)
+    }
+
+    pub fn get_frame(&self) -> &[u16] {
+        &self.frame
+    }
+
+    pub fn get_frame_count(&self) -> usize {
+        self.frame_count
+    }
+}
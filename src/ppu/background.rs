@@ -0,0 +1,108 @@
+use crate::nes_parser::Cartridge;
+use crate::ppu::*;
+
+/*
+Background fetches happen in groups of 8 dots, 2 dots per memory access:
+    1 - nametable byte, 3 - attribute byte, 5 - pattern low, 7 - pattern high
+The fetched tile is loaded into the low byte of the shifters at the start of the next group
+(dots 9, 17, ..., 257 and 329, 337), so the shifters always hold the current tile in their
+high byte and the next one in the low.
+*/
+
+impl Ppu {
+    fn bg_fetch(&mut self, crt: &mut Cartridge) {
+        match self.dot % 8 {
+            1 => {
+                self.bg_next_tile_id = self.ppu_read(0x2000 | (self.v & 0x0FFF), crt);
+            }
+            3 => {
+                let attrib = self.ppu_read(
+                    0x23C0 | (self.v & 0x0C00) | ((self.v >> 4) & 0x38) | ((self.v >> 2) & 0x07),
+                    crt,
+                );
+                // Each attribute byte covers 4x4 tiles, 2 bits for every 2x2 quadrant
+                let shift = ((self.v >> 4) & 4) | (self.v & 2);
+                self.bg_next_tile_attrib = (attrib >> shift) & 3;
+            }
+            5 => {
+                self.bg_next_tile_lsb = self.ppu_read(self.bg_pattern_addr(), crt);
+            }
+            7 => {
+                self.bg_next_tile_msb = self.ppu_read(self.bg_pattern_addr() + 8, crt);
+            }
+            0 => self.increment_coarse_x(),
+            _ => (),
+        }
+    }
+
+    fn bg_pattern_addr(&self) -> u16 {
+        let table = if self.ctrl.contains(PpuCtrl::BACKGROUND_PATTERN) {
+            0x1000
+        } else {
+            0
+        };
+        // Fine Y picks the row inside the tile
+        table + self.bg_next_tile_id as u16 * 16 + ((self.v >> 12) & 7)
+    }
+
+    fn bg_load_shifters(&mut self) {
+        self.bg_shifter_pattern_lo = (self.bg_shifter_pattern_lo & 0xFF00) | self.bg_next_tile_lsb as u16;
+        self.bg_shifter_pattern_hi = (self.bg_shifter_pattern_hi & 0xFF00) | self.bg_next_tile_msb as u16;
+        self.bg_attrib_latch_lo = self.bg_next_tile_attrib & 1 != 0;
+        self.bg_attrib_latch_hi = self.bg_next_tile_attrib & 2 != 0;
+    }
+
+    fn bg_update_shifters(&mut self) {
+        self.bg_shifter_pattern_lo <<= 1;
+        self.bg_shifter_pattern_hi <<= 1;
+        // The attribute shifters are only 8 bits wide and keep getting fed from the latches
+        self.bg_shifter_attrib_lo = (self.bg_shifter_attrib_lo << 1) | self.bg_attrib_latch_lo as u8;
+        self.bg_shifter_attrib_hi = (self.bg_shifter_attrib_hi << 1) | self.bg_attrib_latch_hi as u8;
+    }
+
+    // Runs the background half of the pipeline for the current dot
+    pub(super) fn bg_clock(&mut self, crt: &mut Cartridge) {
+        match self.dot {
+            2..=257 | 322..=337 => self.bg_update_shifters(),
+            _ => (),
+        }
+
+        match self.dot {
+            9..=257 | 329..=337 if self.dot % 8 == 1 => self.bg_load_shifters(),
+            _ => (),
+        }
+
+        match self.dot {
+            1..=256 | 321..=336 => self.bg_fetch(crt),
+            // Unused nametable fetches at the end of the line
+            337 | 339 => {
+                self.ppu_read(0x2000 | (self.v & 0x0FFF), crt);
+            }
+            _ => (),
+        }
+
+        match self.dot {
+            256 => self.increment_y(),
+            257 => self.copy_horizontal(),
+            280..=304 if self.scanline == PRE_RENDER_SCANLINE => self.copy_vertical(),
+            _ => (),
+        }
+    }
+
+    // Returns the 2 bit pixel and 2 bit palette of the background at the current dot
+    pub(super) fn bg_pixel(&self) -> (u8, u8) {
+        if !self.mask.contains(PpuMask::SHOW_BACKGROUND) {
+            return (0, 0);
+        }
+
+        let pattern_bit = 0x8000 >> self.fine_x;
+        let attrib_bit = 0x80 >> self.fine_x;
+
+        let pixel = ((self.bg_shifter_pattern_hi & pattern_bit != 0) as u8) << 1
+            | (self.bg_shifter_pattern_lo & pattern_bit != 0) as u8;
+        let palette = ((self.bg_shifter_attrib_hi & attrib_bit != 0) as u8) << 1
+            | (self.bg_shifter_attrib_lo & attrib_bit != 0) as u8;
+
+        (pixel, palette)
+    }
+}
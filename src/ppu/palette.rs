@@ -0,0 +1,93 @@
+use std::fs;
+use std::io;
+
+/*
+The PPU outputs 6 bit colour indices plus the 3 PPUMASK emphasis bits, and it's up to us
+to turn those into RGB. Standard .pal files come in 2 sizes:
+    192 bytes  - 64 RGB triplets, the emphasis variants get generated
+    1536 bytes - 512 RGB triplets, all 8 emphasis combinations (index = emphasis << 6 | colour)
+*/
+
+pub const PALETTE_SIZE: usize = 64;
+pub const EMPHASIS_VARIANTS: usize = 8;
+
+// Non emphasised channels are darkened by roughly this much
+const EMPHASIS_ATTENUATION: f32 = 0.816;
+
+// 2C02 NTSC colours
+const NTSC_2C02: [[u8; 3]; PALETTE_SIZE] = [
+    [84, 84, 84], [0, 30, 116], [8, 16, 144], [48, 0, 136],
+    [68, 0, 100], [92, 0, 48], [84, 4, 0], [60, 24, 0],
+    [32, 42, 0], [8, 58, 0], [0, 64, 0], [0, 60, 0],
+    [0, 50, 60], [0, 0, 0], [0, 0, 0], [0, 0, 0],
+    [152, 150, 152], [8, 76, 196], [48, 50, 236], [92, 30, 228],
+    [136, 20, 176], [160, 20, 100], [152, 34, 32], [120, 60, 0],
+    [84, 90, 0], [40, 114, 0], [8, 124, 0], [0, 118, 40],
+    [0, 102, 120], [0, 0, 0], [0, 0, 0], [0, 0, 0],
+    [236, 238, 236], [76, 154, 236], [120, 124, 236], [176, 98, 236],
+    [228, 84, 236], [236, 88, 180], [236, 106, 100], [212, 136, 32],
+    [160, 170, 0], [116, 196, 0], [76, 208, 32], [56, 204, 108],
+    [56, 180, 204], [60, 60, 60], [0, 0, 0], [0, 0, 0],
+    [236, 238, 236], [168, 204, 236], [188, 188, 236], [212, 178, 236],
+    [236, 174, 236], [236, 174, 212], [236, 180, 176], [228, 196, 144],
+    [204, 210, 120], [180, 222, 120], [168, 226, 144], [152, 226, 180],
+    [160, 214, 228], [160, 162, 160], [0, 0, 0], [0, 0, 0],
+];
+
+pub struct SystemPalette {
+    colours: Vec<[u8; 3]>,
+}
+
+impl Default for SystemPalette {
+    fn default() -> Self {
+        SystemPalette::from_base_colours(&NTSC_2C02)
+    }
+}
+
+impl SystemPalette {
+    fn from_base_colours(base: &[[u8; 3]]) -> Self {
+        let mut colours = Vec::with_capacity(PALETTE_SIZE * EMPHASIS_VARIANTS);
+
+        for emphasis in 0..EMPHASIS_VARIANTS {
+            for colour in base {
+                let mut colour = *colour;
+                if emphasis != 0 {
+                    // Bit 0 emphasises red, 1 green and 2 blue, everything else gets darker
+                    for (channel, value) in colour.iter_mut().enumerate() {
+                        if emphasis & (1 << channel) == 0 {
+                            *value = (*value as f32 * EMPHASIS_ATTENUATION) as u8;
+                        }
+                    }
+                }
+                colours.push(colour);
+            }
+        }
+
+        SystemPalette { colours }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let colours: Vec<[u8; 3]> = bytes
+            .chunks_exact(3)
+            .map(|rgb| [rgb[0], rgb[1], rgb[2]])
+            .collect();
+
+        match bytes.len() {
+            192 => Ok(SystemPalette::from_base_colours(&colours)),
+            1536 => Ok(SystemPalette { colours }),
+            len => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Palette files should be 192 or 1536 bytes long, got {}", len),
+            )),
+        }
+    }
+
+    pub fn from_file(filename: &str) -> io::Result<Self> {
+        SystemPalette::from_bytes(&fs::read(filename)?)
+    }
+
+    // Takes a colour index with the emphasis bits on top, as found in the PPU's frame
+    pub fn get_rgb(&self, index: u16) -> [u8; 3] {
+        self.colours[index as usize % (PALETTE_SIZE * EMPHASIS_VARIANTS)]
+    }
+}
@@ -0,0 +1,104 @@
+use crate::nes_parser::Cartridge;
+use crate::ppu::*;
+
+/*
+CPU facing PPU registers, mirrored every 8 bytes across $2000-$3FFF:
+$2000 PPUCTRL   (W)     $2004 OAMDATA   (RW)
+$2001 PPUMASK   (W)     $2005 PPUSCROLL (Wx2)
+$2002 PPUSTATUS (R)     $2006 PPUADDR   (Wx2)
+$2003 OAMADDR   (W)     $2007 PPUDATA   (RW)
+*/
+
+impl Ppu {
+    pub fn cpu_read(&mut self, addr: u16, crt: &mut Cartridge) -> u8 {
+        match addr & 0x7 {
+            2 => {
+                // Only the top 3 bits are driven, the rest is whatever was last on the bus
+                let value = self.status.bits() | (self.open_bus & 0x1F);
+                self.status.remove(PpuStatus::VBLANK);
+                self.write_toggle = false;
+                self.open_bus = value;
+            }
+            4 => {
+                self.open_bus = self.oam[self.oam_addr as usize];
+            }
+            7 => {
+                let addr = self.v & 0x3FFF;
+                self.open_bus = if addr >= 0x3F00 {
+                    // Palette reads skip the buffer, but it still gets the nametable "under" them
+                    self.read_buffer = self.ppu_read(addr - 0x1000, crt);
+                    let mut colour = self.ppu_read(addr, crt) & 0x3F;
+                    if self.mask.contains(PpuMask::GREYSCALE) {
+                        colour &= 0x30;
+                    }
+                    colour | (self.open_bus & 0xC0)
+                } else {
+                    let value = self.read_buffer;
+                    self.read_buffer = self.ppu_read(addr, crt);
+                    value
+                };
+                self.increment_vram_addr();
+            }
+            _ => (), // Write only, reads the open bus
+        }
+
+        self.open_bus
+    }
+
+    pub fn cpu_write(&mut self, addr: u16, value: u8, crt: &mut Cartridge) {
+        self.open_bus = value;
+
+        match addr & 0x7 {
+            0 => {
+                self.ctrl = PpuCtrl::from_bits_truncate(value);
+                self.set_t_nametable(value);
+            }
+            1 => self.mask = PpuMask::from_bits_truncate(value),
+            2 => (), // Read only
+            3 => self.oam_addr = value,
+            4 => {
+                self.oam[self.oam_addr as usize] = value;
+                self.oam_addr = self.oam_addr.wrapping_add(1);
+            }
+            5 => {
+                if self.write_toggle {
+                    self.set_t_scroll_y(value);
+                } else {
+                    self.set_t_scroll_x(value);
+                }
+                self.write_toggle = !self.write_toggle;
+            }
+            6 => {
+                if self.write_toggle {
+                    self.t = (self.t & 0xFF00) | value as u16;
+                    self.v = self.t;
+                } else {
+                    // The top bit of the 15 bit t gets cleared here too
+                    self.t = (self.t & 0x00FF) | (((value & 0x3F) as u16) << 8);
+                }
+                self.write_toggle = !self.write_toggle;
+            }
+            7 => {
+                self.ppu_write(self.v, value, crt);
+                self.increment_vram_addr();
+            }
+            _ => unreachable!("PPU register match failed - impossible!"),
+        }
+    }
+
+    fn increment_vram_addr(&mut self) {
+        if self.is_rendering() {
+            // Accessing PPUDATA mid render bumps v through both rendering increments instead
+            self.increment_coarse_x();
+            self.increment_y();
+            return;
+        }
+
+        let step = if self.ctrl.contains(PpuCtrl::VRAM_INCREMENT) {
+            32
+        } else {
+            1
+        };
+        self.v = self.v.wrapping_add(step) & 0x7FFF;
+    }
+}
@@ -0,0 +1,76 @@
+use crate::ppu::*;
+
+/*
+The internal VRAM address (v) and the temporary address (t) share this layout:
+    yyy NN YYYYY XXXXX
+    ||| || ||||| +++++-- coarse X scroll
+    ||| || +++++-------- coarse Y scroll
+    ||| ++-------------- nametable select
+    +++----------------- fine Y scroll
+While rendering v is the address of the tile being fetched, CPU writes only touch t and
+get copied into v at fixed dots, which is what makes mid frame scroll splits work.
+*/
+
+const COARSE_X: u16 = 0x001F;
+const COARSE_Y: u16 = 0x03E0;
+const NAMETABLE_X: u16 = 0x0400;
+const NAMETABLE_Y: u16 = 0x0800;
+const FINE_Y: u16 = 0x7000;
+
+const HORIZONTAL_BITS: u16 = COARSE_X | NAMETABLE_X;
+const VERTICAL_BITS: u16 = COARSE_Y | NAMETABLE_Y | FINE_Y;
+
+impl Ppu {
+    pub(super) fn increment_coarse_x(&mut self) {
+        if self.v & COARSE_X == 31 {
+            self.v &= !COARSE_X;
+            self.v ^= NAMETABLE_X;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    pub(super) fn increment_y(&mut self) {
+        if self.v & FINE_Y != FINE_Y {
+            self.v += 0x1000;
+            return;
+        }
+
+        self.v &= !FINE_Y;
+        let mut coarse_y = (self.v & COARSE_Y) >> 5;
+        if coarse_y == 29 {
+            // Last row of a nametable, the attribute table comes next so we skip it
+            coarse_y = 0;
+            self.v ^= NAMETABLE_Y;
+        } else if coarse_y == 31 {
+            // Scrolled into the attribute table, wraps without switching nametables
+            coarse_y = 0;
+        } else {
+            coarse_y += 1;
+        }
+        self.v = (self.v & !COARSE_Y) | (coarse_y << 5);
+    }
+
+    pub(super) fn copy_horizontal(&mut self) {
+        self.v = (self.v & !HORIZONTAL_BITS) | (self.t & HORIZONTAL_BITS);
+    }
+
+    pub(super) fn copy_vertical(&mut self) {
+        self.v = (self.v & !VERTICAL_BITS) | (self.t & VERTICAL_BITS);
+    }
+
+    pub(super) fn set_t_nametable(&mut self, ctrl: u8) {
+        self.t = (self.t & !(NAMETABLE_X | NAMETABLE_Y)) | (((ctrl & 3) as u16) << 10);
+    }
+
+    pub(super) fn set_t_scroll_x(&mut self, value: u8) {
+        self.t = (self.t & !COARSE_X) | (value >> 3) as u16;
+        self.fine_x = value & 7;
+    }
+
+    pub(super) fn set_t_scroll_y(&mut self, value: u8) {
+        self.t = (self.t & !(COARSE_Y | FINE_Y))
+            | (((value >> 3) as u16) << 5)
+            | (((value & 7) as u16) << 12);
+    }
+}
@@ -0,0 +1,205 @@
+use bitflags::bitflags;
+
+use crate::nes_parser::Cartridge;
+use crate::ppu::*;
+
+/*
+Sprites for the next line are chosen while the current one is drawn:
+    dots 1-64    - secondary OAM is cleared to $FF
+    dots 65-256  - OAM is scanned for up to 8 sprites in range (with the overflow bug)
+    dots 257-320 - pattern fetches for the 8 secondary OAM slots, 8 dots per sprite
+We do the clear and the scan in one go at dot 256, the fetches are done at their real dots
+so mappers watching the pattern table address see them in the right order.
+*/
+
+pub const MAX_SPRITES_PER_LINE: usize = 8;
+
+bitflags! {
+    #[derive(Default)]
+    pub struct SpriteAttributes: u8 {
+        const PALETTE =     0b00000011;
+        const PRIORITY =    0b00100000; // 0 - in front of background, 1 - behind
+        const FLIP_H =      0b01000000;
+        const FLIP_V =      0b10000000;
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+pub(super) struct SpriteUnit {
+    x: u8,
+    attrib: SpriteAttributes,
+    pattern_lo: u8,
+    pattern_hi: u8,
+}
+
+pub(super) struct SpritePixel {
+    pub pixel: u8,
+    pub palette: u8,
+    pub behind_background: bool,
+    pub sprite_zero: bool,
+}
+
+impl Ppu {
+    fn sprite_height(&self) -> u16 {
+        if self.ctrl.contains(PpuCtrl::SPRITE_SIZE) {
+            16
+        } else {
+            8
+        }
+    }
+
+    fn sprite_in_range(&self, y: u8) -> bool {
+        let row = self.scanline.wrapping_sub(y as u16);
+        row < self.sprite_height()
+    }
+
+    fn sprite_evaluate(&mut self) {
+        self.secondary_oam = [0xFF; 32];
+        self.sprite_count = 0;
+        self.sprite_zero_next = false;
+
+        let mut n = 0;
+        while n < 64 && self.sprite_count < MAX_SPRITES_PER_LINE {
+            let entry = n * 4;
+            // The Y coordinate gets copied no matter what, the rest only if it's in range
+            self.secondary_oam[self.sprite_count * 4] = self.oam[entry];
+            if self.sprite_in_range(self.oam[entry]) {
+                self.secondary_oam[self.sprite_count * 4..self.sprite_count * 4 + 4]
+                    .copy_from_slice(&self.oam[entry..entry + 4]);
+                if n == 0 {
+                    self.sprite_zero_next = true;
+                }
+                self.sprite_count += 1;
+            }
+            n += 1;
+        }
+
+        // Hardware bug: after 8 sprites the byte offset is incremented along with the sprite
+        // index, so the "Y" checked for overflow is a tile/attribute/X byte most of the time
+        let mut m = 0;
+        while n < 64 {
+            if self.sprite_in_range(self.oam[n * 4 + m]) {
+                self.status.insert(PpuStatus::SPRITE_OVERFLOW);
+                break;
+            }
+            n += 1;
+            m = (m + 1) & 3;
+        }
+    }
+
+    fn sprite_pattern_addr(&self, slot: usize) -> u16 {
+        let (y, tile, attrib) = if slot < self.sprite_count {
+            (
+                self.secondary_oam[slot * 4],
+                self.secondary_oam[slot * 4 + 1],
+                SpriteAttributes::from_bits_truncate(self.secondary_oam[slot * 4 + 2]),
+            )
+        } else {
+            // Empty slots still fetch, tile $FF on row 0
+            (self.scanline as u8, 0xFF, SpriteAttributes::empty())
+        };
+
+        let height = self.sprite_height();
+        let mut row = self.scanline.wrapping_sub(y as u16) & (height - 1);
+        if attrib.contains(SpriteAttributes::FLIP_V) {
+            row = height - 1 - row;
+        }
+
+        if height == 16 {
+            // 8x16 sprites pick their table from bit 0 of the tile, and use 2 consecutive tiles
+            let table = (tile as u16 & 1) * 0x1000;
+            let tile = (tile & 0xFE) as u16 + if row >= 8 { 1 } else { 0 };
+            table + tile * 16 + (row & 7)
+        } else {
+            let table = if self.ctrl.contains(PpuCtrl::SPRITE_PATTERN) {
+                0x1000
+            } else {
+                0
+            };
+            table + tile as u16 * 16 + row
+        }
+    }
+
+    fn sprite_fetch(&mut self, crt: &mut Cartridge) {
+        let slot = ((self.dot - 257) / 8) as usize;
+
+        match (self.dot - 257) % 8 {
+            0 => {
+                // The new line's sprites replace the old ones once the old line is done
+                if slot == 0 {
+                    self.sprite_line_count = self.sprite_count;
+                    self.sprite_zero_on_line = self.sprite_zero_next;
+                }
+                self.sprite_units[slot] = SpriteUnit {
+                    x: self.secondary_oam[slot * 4 + 3],
+                    attrib: SpriteAttributes::from_bits_truncate(self.secondary_oam[slot * 4 + 2]),
+                    pattern_lo: 0,
+                    pattern_hi: 0,
+                };
+            }
+            5 => {
+                let mut pattern = self.ppu_read(self.sprite_pattern_addr(slot), crt);
+                if self.sprite_units[slot].attrib.contains(SpriteAttributes::FLIP_H) {
+                    pattern = pattern.reverse_bits();
+                }
+                self.sprite_units[slot].pattern_lo = pattern;
+            }
+            7 => {
+                let mut pattern = self.ppu_read(self.sprite_pattern_addr(slot) + 8, crt);
+                if self.sprite_units[slot].attrib.contains(SpriteAttributes::FLIP_H) {
+                    pattern = pattern.reverse_bits();
+                }
+                self.sprite_units[slot].pattern_hi = pattern;
+            }
+            _ => (),
+        }
+    }
+
+    // Runs the sprite half of the pipeline for the current dot
+    pub(super) fn sprite_clock(&mut self, crt: &mut Cartridge) {
+        match self.dot {
+            256 => {
+                if self.scanline == PRE_RENDER_SCANLINE {
+                    // Nothing gets evaluated on the pre-render line, so no sprites on line 0
+                    self.sprite_count = 0;
+                    self.sprite_zero_next = false;
+                } else {
+                    self.sprite_evaluate();
+                }
+            }
+            257..=320 => {
+                self.oam_addr = 0;
+                self.sprite_fetch(crt);
+            }
+            _ => (),
+        }
+    }
+
+    // The first opaque sprite pixel at the current dot, lower slots win
+    pub(super) fn sprite_pixel(&self) -> Option<SpritePixel> {
+        if !self.mask.contains(PpuMask::SHOW_SPRITES) {
+            return None;
+        }
+
+        let x = self.dot - 1;
+        for (slot, unit) in self.sprite_units[..self.sprite_line_count].iter().enumerate() {
+            let offset = x.wrapping_sub(unit.x as u16);
+            if offset >= 8 {
+                continue;
+            }
+
+            let bit = 0x80 >> offset;
+            let pixel = ((unit.pattern_hi & bit != 0) as u8) << 1 | (unit.pattern_lo & bit != 0) as u8;
+            if pixel != 0 {
+                return Some(SpritePixel {
+                    pixel,
+                    palette: (unit.attrib & SpriteAttributes::PALETTE).bits() + 4,
+                    behind_background: unit.attrib.contains(SpriteAttributes::PRIORITY),
+                    sprite_zero: slot == 0 && self.sprite_zero_on_line,
+                });
+            }
+        }
+
+        None
+    }
+}
@@ -1,3 +1,4 @@
+use crate::ppu::palette::SystemPalette;
 use pixels::{Pixels, SurfaceTexture};
 use winit::dpi::LogicalSize;
 use winit::event_loop::EventLoop;
@@ -27,3 +28,11 @@ pub fn create_window(width: u32, height: u32, title: &str) -> (EventLoop<()>, Wi
 
     (event_loop, window, pixels)
 }
+
+// Converts a frame of PPU colour indices into the RGBA frame pixels wants
+pub fn draw_frame(rgba: &mut [u8], indices: &[u16], palette: &SystemPalette) {
+    for (pixel, &index) in rgba.chunks_exact_mut(4).zip(indices) {
+        let [r, g, b] = palette.get_rgb(index);
+        pixel.copy_from_slice(&[r, g, b, 0xff]);
+    }
+}